@@ -1,4 +1,4 @@
-use std::{fs, collections::HashMap};
+use std::{env, fmt, fs, collections::{HashMap, HashSet}};
 use lazy_static::lazy_static;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -39,17 +39,81 @@ lazy_static! {
     };
 }
 
+const DIRECTIONS: [Point; 4] = [
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+    Point { x: -1, y: 0 },
+    Point { x: 1, y: 0 },
+];
+
+#[derive(Debug, PartialEq)]
+enum LoopError {
+    MissingStart,
+    NoLoop,
+    AmbiguousStart(Vec<char>),
+    CountMismatch { scanline: usize, picks: i64 },
+}
+
+impl fmt::Display for LoopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopError::MissingStart => write!(f, "the maze has no start tile 'S'"),
+            LoopError::NoLoop => write!(f, "no pipe loop passes through the start tile"),
+            LoopError::AmbiguousStart(pipes) => write!(
+                f,
+                "the start tile could be any of {:?}, each closing a different loop",
+                pipes
+            ),
+            LoopError::CountMismatch { scanline, picks } => write!(
+                f,
+                "scanline found {} enclosed tiles but Pick's theorem gives {}",
+                scanline, picks
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tile {
+    Loop,
+    Inside,
+    Outside,
+}
+
+#[derive(Debug, PartialEq)]
+struct PipeLoop {
+    start_pipe: char,
+    points: Vec<Point>,
+}
+
 fn main() {
     let file_path = "input.txt";
 
     let contents = fs::read_to_string(file_path).unwrap();
 
-    println!("Steps part 1: {}", part_1(&contents));
-    println!("Steps part 2: {}", part_2(&contents));
+    if env::args().any(|arg| arg == "--render") {
+        let grid = file_to_grid(&contents);
+        match find_loop(&grid) {
+            Ok(pipe_loop) => {
+                let tiles = classify_tiles(&grid, &pipe_loop, grid_size(&contents));
+                println!("{}", render_tiles(&grid, &pipe_loop, &tiles, true));
+            }
+            Err(err) => println!("Render failed: {}", err),
+        }
+    }
+
+    match part_1(&contents) {
+        Ok(steps) => println!("Steps part 1: {}", steps),
+        Err(err) => println!("Part 1 failed: {}", err),
+    }
+    match part_2(&contents) {
+        Ok(tiles) => println!("Steps part 2: {}", tiles),
+        Err(err) => println!("Part 2 failed: {}", err),
+    }
 }
 
 fn file_to_grid(
-    contents: &String,
+    contents: &str,
 ) -> HashMap<Point, char> {
     let mut grid = HashMap::new();
 
@@ -64,130 +128,215 @@ fn file_to_grid(
     grid
 }
 
+fn grid_size(
+    contents: &str,
+) -> (usize, usize) {
+    let width = contents.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+
+    (width, contents.lines().count())
+}
+
 fn find_start_point(
     grid: &HashMap<Point, char>,
-) -> Point {
-    *grid.iter().find(|(_, &c)| c == 'S').unwrap().0
+) -> Result<Point, LoopError> {
+    grid.iter()
+        .find(|(_, &c)| c == 'S')
+        .map(|(&point, _)| point)
+        .ok_or(LoopError::MissingStart)
 }
 
-fn find_loop(
+/// Follows the pipes from `start_point`, treating it as `start_pipe`, and returns the
+/// tiles of the loop in walking order, or `None` if the pipes don't lead back to it.
+fn trace_loop(
     grid: &HashMap<Point, char>,
-    start_point: &Point,
-    point: Point,
-    direction: Point,
-    length: u32,
-    border: &mut Option<&mut Vec<Point>>,
-) -> u32 {
-    let current_point = point.add(direction);
+    start_point: Point,
+    start_pipe: char,
+) -> Option<Vec<Point>> {
+    let start_directions = PIPE_DIRECTIONS.get(&start_pipe)?;
 
-    if *start_point == current_point {
-        return length + 1
-    }
+    let mut points = vec![start_point];
+    let mut current_point = start_point;
+    let mut direction = start_directions[0];
 
-    let pipe = match grid.get(&current_point) {
-        Some(pipe) => pipe,
-        None => return 0,
-    };
+    loop {
+        current_point = current_point.add(direction);
+
+        if current_point == start_point {
+            return start_directions.contains(&direction.get_opposite()).then_some(points);
+        }
 
-    let directions = PIPE_DIRECTIONS.get(pipe).unwrap();
+        let directions = PIPE_DIRECTIONS.get(grid.get(&current_point)?)?;
 
-    if !directions.contains(&direction.get_opposite()) {
-        return 0
+        if !directions.contains(&direction.get_opposite()) || points.len() > grid.len() {
+            return None;
+        }
+
+        direction = if directions[0] == direction.get_opposite() {
+            directions[1]
+        } else {
+            directions[0]
+        };
+
+        points.push(current_point);
     }
+}
 
-    let next_direction = if directions[0] == direction.get_opposite() {
-        directions[1]
-    } else {
-        directions[0]
-    };
+/// Infers the pipe hidden under `S` from the neighbours connecting back to it and
+/// traces the loop it closes.
+fn find_loop(
+    grid: &HashMap<Point, char>,
+) -> Result<PipeLoop, LoopError> {
+    let start_point = find_start_point(grid)?;
+
+    let connected: Vec<Point> = DIRECTIONS.iter()
+        .copied()
+        .filter(|&direction| {
+            grid.get(&start_point.add(direction))
+                .and_then(|pipe| PIPE_DIRECTIONS.get(pipe))
+                .is_some_and(|directions| directions.contains(&direction.get_opposite()))
+        })
+        .collect();
 
-    if let Some(border_vec) = border {
-        border_vec.push(current_point);
+    let mut loops: Vec<PipeLoop> = PIPE_DIRECTIONS.iter()
+        .filter(|(_, directions)| directions.iter().all(|direction| connected.contains(direction)))
+        .filter_map(|(&start_pipe, _)| {
+            trace_loop(grid, start_point, start_pipe)
+                .map(|points| PipeLoop { start_pipe, points })
+        })
+        .collect();
+
+    match loops.len() {
+        0 => Err(LoopError::NoLoop),
+        1 => Ok(loops.remove(0)),
+        _ => {
+            let mut pipes: Vec<char> = loops.iter().map(|pipe_loop| pipe_loop.start_pipe).collect();
+            pipes.sort();
+            Err(LoopError::AmbiguousStart(pipes))
+        }
+    }
+}
+
+/// Labels every tile with an even-odd scanline. Only loop tiles connecting north
+/// toggle the state, so `F--J` and `L--7` cross the loop once while `F--7` and
+/// `L--J` only touch it.
+fn classify_tiles(
+    grid: &HashMap<Point, char>,
+    pipe_loop: &PipeLoop,
+    (width, height): (usize, usize),
+) -> Vec<Vec<Tile>> {
+    let loop_points: HashSet<Point> = pipe_loop.points.iter().copied().collect();
+
+    (0..height)
+        .map(|y| {
+            let mut inside = false;
+            (0..width)
+                .map(|x| {
+                    let point = Point::new(x as i32, y as i32);
+                    if !loop_points.contains(&point) {
+                        return if inside { Tile::Inside } else { Tile::Outside };
+                    }
+
+                    let pipe = if grid[&point] == 'S' { pipe_loop.start_pipe } else { grid[&point] };
+                    if PIPE_DIRECTIONS[&pipe].contains(&Point::new(0, -1)) {
+                        inside = !inside;
+                    }
+                    Tile::Loop
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn box_drawing(pipe: char) -> char {
+    match pipe {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        other => other,
     }
+}
+
+/// Draws the loop with box-drawing characters, inside tiles as `I` and outside tiles
+/// as `O`. With `colour` set the start tile, loop and inside tiles are highlighted.
+fn render_tiles(
+    grid: &HashMap<Point, char>,
+    pipe_loop: &PipeLoop,
+    tiles: &[Vec<Tile>],
+    colour: bool,
+) -> String {
+    let paint = |text: String, code: &str| {
+        if colour { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text }
+    };
 
-    find_loop(
-        grid, 
-        start_point, 
-        current_point, 
-        next_direction, 
-        length + 1, 
-        border
-    )
+    tiles.iter()
+        .enumerate()
+        .map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(x, tile)| {
+                    let point = Point::new(x as i32, y as i32);
+                    match tile {
+                        Tile::Loop if point == pipe_loop.points[0] => {
+                            paint(box_drawing(pipe_loop.start_pipe).to_string(), "1;31")
+                        }
+                        Tile::Loop => paint(box_drawing(grid[&point]).to_string(), "33"),
+                        Tile::Inside => paint("I".to_string(), "1;32"),
+                        Tile::Outside => paint("O".to_string(), "2"),
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-fn calculate_area(points: &Vec<Point>) -> i32 {
-    let mut area = 0.0;
+fn calculate_area(points: &[Point]) -> i64 {
     let n = points.len();
 
     // Calculate area using the Shoelace formula
-    for i in 0..n {
-        let j = (i + 1) % n;
-        area += (points[i].x as f64 * points[j].y as f64) - (points[j].x as f64 * points[i].y as f64);
-    }
-
-    dbg!(area.abs() / 2.0);
+    let double_area: i64 = (0..n)
+        .map(|i| {
+            let j = (i + 1) % n;
+            points[i].x as i64 * points[j].y as i64 - points[j].x as i64 * points[i].y as i64
+        })
+        .sum();
 
-    (area.abs() / 2.0) as i32
+    double_area.abs() / 2
 }
 
 fn part_1(
-    contents: &String
-) -> i64 {
+    contents: &str
+) -> Result<i64, LoopError> {
     let grid = file_to_grid(contents);
-    let start_point = find_start_point(&grid);
-
-    let directions = [
-        Point::new(0, -1),
-        Point::new(0, 1),
-        Point::new(-1, 0),
-        Point::new(1, 0),
-    ];
 
-    let max_length = directions.iter()
-        .map(|&direction| find_loop(
-                                    &grid, 
-                                    &start_point, 
-                                    start_point, 
-                                    direction, 
-                                    0, 
-                                    &mut None))
-        .max()
-        .unwrap();
+    let pipe_loop = find_loop(&grid)?;
 
-    (max_length/2) as i64
+    Ok(pipe_loop.points.len() as i64 / 2)
 }
 
 fn part_2(
-    contents: &String
-) -> i64 {
+    contents: &str
+) -> Result<i64, LoopError> {
     let grid = file_to_grid(contents);
-    let start_point = find_start_point(&grid);
-
-    let mut directions = [
-        (Point::new(0, -1), &mut Vec::new()),
-        (Point::new(0, 1), &mut Vec::new()),
-        (Point::new(-1, 0), &mut Vec::new()),
-        (Point::new(1, 0), &mut Vec::new()),
-    ];
-
-    let (max_length, longest_vec) = directions.iter_mut()
-        .map(|(direction, border)| {
-            let length = find_loop(
-                                &grid, 
-                                &start_point, 
-                                start_point, 
-                                *direction, 
-                                0, 
-                                &mut Some(border));
-            (length, border)
-        })
-        .max_by_key(|&(length, _)| length)
-        .unwrap();
+
+    let pipe_loop = find_loop(&grid)?;
 
     // Calculate the number of points inside using the Pick's theorem
-    let res = calculate_area(longest_vec) - max_length as i32/2 + 1;
-    
-    res as i64
+    let picks = calculate_area(&pipe_loop.points) - pipe_loop.points.len() as i64 / 2 + 1;
+
+    let scanline = classify_tiles(&grid, &pipe_loop, grid_size(contents)).iter()
+        .flatten()
+        .filter(|&&tile| tile == Tile::Inside)
+        .count();
+
+    if scanline as i64 != picks {
+        return Err(LoopError::CountMismatch { scanline, picks });
+    }
+
+    Ok(picks)
 }
 
 #[cfg(test)]
@@ -200,7 +349,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_1(&contents), 4);
+        assert_eq!(part_1(&contents), Ok(4));
     }
 
     #[test]
@@ -209,7 +358,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_1(&contents), 8);
+        assert_eq!(part_1(&contents), Ok(8));
     }
     #[test]
     fn test_part_2_first_input() {
@@ -217,7 +366,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 4);
+        assert_eq!(part_2(&contents), Ok(4));
     }
 
     #[test]
@@ -226,6 +375,35 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 10);
+        assert_eq!(part_2(&contents), Ok(10));
+    }
+
+    #[test]
+    fn test_find_loop() {
+        let grid = file_to_grid("-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF");
+
+        let pipe_loop = find_loop(&grid).unwrap();
+        assert_eq!(pipe_loop.start_pipe, 'F');
+        assert_eq!(pipe_loop.points.len(), 8);
+        assert_eq!(pipe_loop.points[0], Point::new(1, 1));
+
+        assert_eq!(find_loop(&file_to_grid("F7.\nLS7\n.LJ")), Err(LoopError::AmbiguousStart(vec!['F', 'J'])));
+        assert_eq!(find_loop(&file_to_grid("S-.\n...")), Err(LoopError::NoLoop));
+        assert_eq!(find_loop(&file_to_grid("F7\nLJ")), Err(LoopError::MissingStart));
+    }
+
+    #[test]
+    fn test_classify_tiles() {
+        let contents = fs::read_to_string("test_input_1_part_2.txt").unwrap();
+
+        let grid = file_to_grid(&contents);
+        let pipe_loop = find_loop(&grid).unwrap();
+        let tiles = classify_tiles(&grid, &pipe_loop, grid_size(&contents));
+
+        assert_eq!(tiles[6][1..6], [Tile::Loop, Tile::Inside, Tile::Inside, Tile::Loop, Tile::Outside]);
+        assert_eq!(
+            render_tiles(&grid, &pipe_loop, &tiles, false).lines().nth(6),
+            Some("O│II│O│II│O")
+        );
     }
 }
@@ -1,4 +1,4 @@
-use std::{fs, collections::BTreeMap};
+use std::{env, fs, collections::BTreeMap};
 use itertools::Itertools;
 
 #[derive(Debug)]
@@ -11,42 +11,82 @@ impl Point {
     fn new(x: i64, y: i64) -> Self {
         Self { x, y }
     }
+
+    fn distance(&self, other: &Self) -> i64 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
 }
 
+/// Above this many galaxies the CSV distance matrix is refused, it grows quadratically.
+const MAX_MATRIX_GALAXIES: usize = 500;
+
 fn main() {
     let file_path = "input.txt";
 
     let contents = fs::read_to_string(file_path).unwrap();
 
     println!("Sum part 1: {}", part_1(&contents));
-    println!("Sum part 2: {}", part_2(&contents));
+    println!("Sum part 2: {}", part_2(&contents, 1_000_000));
+
+    let args: Vec<String> = env::args().collect();
+    let arg_value = |flag: &str, offset: usize| {
+        args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + offset))
+    };
+
+    let expansion_factor = arg_value("--expansion", 1).map_or(2, |factor| factor.parse().unwrap());
+    let grid = file_to_grid(&contents, expansion_factor);
+
+    if let Some((a, b, distance)) = farthest_pair(&grid) {
+        println!("Farthest pair: galaxies {} and {} at distance {}", a, b, distance);
+    }
+
+    if let (Some(galaxy), Some(k)) = (arg_value("--nearest", 1), arg_value("--nearest", 2)) {
+        match nearest_galaxies(&grid, galaxy.parse().unwrap(), k.parse().unwrap()) {
+            Some(nearest) => nearest.iter().for_each(|(other, distance)| {
+                println!("Galaxy {} at distance {}", other, distance);
+            }),
+            None => println!("There is no galaxy {}", galaxy),
+        }
+    }
+
+    if let Some(bucket_width) = arg_value("--histogram", 1) {
+        match distance_histogram(&grid, bucket_width.parse().unwrap()) {
+            Some(histogram) => histogram.iter().for_each(|(bucket, count)| println!("{:>8}: {}", bucket, count)),
+            None => println!("Histogram bucket width must be positive"),
+        }
+    }
+
+    if let Some(csv_path) = arg_value("--csv", 1) {
+        match distance_matrix_csv(&grid) {
+            Some(csv) => fs::write(csv_path, csv).unwrap(),
+            None => println!("Too many galaxies for a distance matrix (limit {})", MAX_MATRIX_GALAXIES),
+        }
+    }
 }
 
+/// Galaxy positions after every empty row and column has been replaced by
+/// `expansion_factor` empty rows or columns. Galaxies are numbered from 1 in reading order.
 fn file_to_grid(
-    contents: &String,
-    multiplier: i64,
+    contents: &str,
+    expansion_factor: i64,
 ) -> BTreeMap<u32, Point> {
     let mut grid = BTreeMap::new();
 
-    let twice_vertical = twice_vertical(contents);
-    let twice_horizontal = twice_horizontal(contents);
+    let (empty_rows, empty_columns) = empty_rows_and_columns(contents);
+    let row_offsets = expansion_offsets(&empty_rows, expansion_factor);
+    let column_offsets = expansion_offsets(&empty_columns, expansion_factor);
 
     let mut counter = 0;
 
     contents.lines().enumerate().for_each(|(i, line)| {
-        line.chars().enumerate().for_each(|(j, c)| {
-            if c == '#' {
+        line.bytes().enumerate().for_each(|(j, c)| {
+            if c == b'#' {
                 counter += 1;
-                let plus_x = twice_vertical.iter()
-                                    .filter(|&&x| x < j as i64).count() as i64;
-                let plus_y = twice_horizontal.iter()
-                                    .filter(|&&y| y < i as i64).count() as i64;
-                
                 grid.insert(
                     counter, 
                     Point::new(
-                        j as i64 + plus_x * multiplier, 
-                        i as i64 + plus_y * multiplier));
+                        j as i64 + column_offsets[j], 
+                        i as i64 + row_offsets[i]));
             }
         });
     });
@@ -54,70 +94,158 @@ fn file_to_grid(
     grid
 }
 
-fn twice_horizontal(
-    contents: &String,
-) -> Vec<i64> {
-    contents.lines().enumerate()
-        .filter_map(|(i, line)| {
-            if line.chars().all(|c| c == '.') {
-                Some(i as i64)
-            } else {
-                None
+/// Marks which rows and columns contain no galaxy, in a single pass over the image.
+fn empty_rows_and_columns(
+    contents: &str,
+) -> (Vec<bool>, Vec<bool>) {
+    let num_columns = contents.lines().map(|line| line.len()).max().unwrap_or(0);
+
+    let mut empty_rows = Vec::new();
+    let mut empty_columns = vec![true; num_columns];
+
+    contents.lines().for_each(|line| {
+        let mut empty_row = true;
+        line.bytes().enumerate().for_each(|(j, c)| {
+            if c == b'#' {
+                empty_row = false;
+                empty_columns[j] = false;
             }
-        })
-        .collect_vec()
+        });
+        empty_rows.push(empty_row);
+    });
+
+    (empty_rows, empty_columns)
 }
 
-fn twice_vertical(
-    contents: &String,
+/// How far each row (or column) moves once the empty ones before it have expanded.
+fn expansion_offsets(
+    empty: &[bool],
+    expansion_factor: i64,
 ) -> Vec<i64> {
-    let lines: Vec<&str> = contents.lines().collect();
-    let num_columns = lines[0].len();
-
-    (0..num_columns)
-        .filter_map(|i| {
-            if lines.iter().all(|line| line.chars().nth(i).unwrap() == '.') {
-                Some(i as i64)
-            } else {
-                None
+    empty.iter()
+        .scan(0, |empty_before, &is_empty| {
+            let offset = *empty_before * (expansion_factor - 1);
+            if is_empty {
+                *empty_before += 1;
             }
+            Some(offset)
         })
         .collect()
 }
 
-fn part_1(
-    contents: &String
+/// Sum of Manhattan distances over all pairs, computed per axis from sorted
+/// coordinates and running prefix sums in O(n log n).
+fn sum_pairwise_distances(
+    grid: &BTreeMap<u32, Point>,
 ) -> i64 {
-    let grid = file_to_grid(contents, 1);
+    let axis_sum = |mut coordinates: Vec<i64>| -> i64 {
+        coordinates.sort_unstable();
+        coordinates.iter()
+            .enumerate()
+            .scan(0, |prefix_sum, (i, &coordinate)| {
+                let distances = coordinate * i as i64 - *prefix_sum;
+                *prefix_sum += coordinate;
+                Some(distances)
+            })
+            .sum()
+    };
+
+    axis_sum(grid.values().map(|point| point.x).collect())
+        + axis_sum(grid.values().map(|point| point.y).collect())
+}
 
-    grid.keys().combinations(2)
-        .map(|pair| {
-            let a =  grid.get(&pair[0]).unwrap();
-            let b = grid.get(&pair[1]).unwrap();
-            let x = (a.x - b.x).abs();
-            let y = (a.y - b.y).abs();
+/// The `k` galaxies closest to `galaxy`, nearest first and ties broken by number.
+fn nearest_galaxies(
+    grid: &BTreeMap<u32, Point>,
+    galaxy: u32,
+    k: usize,
+) -> Option<Vec<(u32, i64)>> {
+    let point = grid.get(&galaxy)?;
 
-            x + y
-        })
-        .sum()
+    Some(grid.iter()
+        .filter(|(&other, _)| other != galaxy)
+        .map(|(&other, other_point)| (other, point.distance(other_point)))
+        .sorted_by_key(|&(other, distance)| (distance, other))
+        .take(k)
+        .collect())
 }
 
-fn part_2(
-    contents: &String
+/// The pair of galaxies furthest apart. Manhattan distance is the larger spread of
+/// `x + y` or `x - y`, so only the extremes of those need to be found.
+fn farthest_pair(
+    grid: &BTreeMap<u32, Point>,
+) -> Option<(u32, u32, i64)> {
+    let spread = |rotate: fn(&Point) -> i64| {
+        let (min, max) = grid.iter()
+            .minmax_by_key(|(_, point)| rotate(point))
+            .into_option()?;
+        let (a, b) = if min.0 < max.0 { (min.0, max.0) } else { (max.0, min.0) };
+        Some((*a, *b, rotate(max.1) - rotate(min.1)))
+    };
+
+    let sum = spread(|point| point.x + point.y)?;
+    let difference = spread(|point| point.x - point.y)?;
+
+    if sum.0 == sum.1 {
+        return None;
+    }
+
+    Some(if difference.2 > sum.2 { difference } else { sum })
+}
+
+/// Counts pairwise distances in buckets of `bucket_width`, keyed by each bucket's lower bound,
+/// or `None` when the width isn't positive.
+fn distance_histogram(
+    grid: &BTreeMap<u32, Point>,
+    bucket_width: i64,
+) -> Option<BTreeMap<i64, usize>> {
+    if bucket_width <= 0 {
+        return None;
+    }
+
+    let mut histogram = BTreeMap::new();
+
+    grid.values().tuple_combinations().for_each(|(a, b)| {
+        let distance = a.distance(b);
+        *histogram.entry(distance - distance % bucket_width).or_insert(0) += 1;
+    });
+
+    Some(histogram)
+}
+
+/// Full distance matrix with galaxy numbers as headers, or `None` above `MAX_MATRIX_GALAXIES`.
+fn distance_matrix_csv(
+    grid: &BTreeMap<u32, Point>,
+) -> Option<String> {
+    if grid.len() > MAX_MATRIX_GALAXIES {
+        return None;
+    }
+
+    let mut csv = format!("galaxy,{}\n", grid.keys().join(","));
+
+    for (galaxy, point) in grid {
+        let distances = grid.values().map(|other| point.distance(other)).join(",");
+        csv.push_str(&format!("{},{}\n", galaxy, distances));
+    }
+
+    Some(csv)
+}
+
+fn part_1(
+    contents: &str
 ) -> i64 {
-    
-    let grid = file_to_grid(contents, 999999);
+    let grid = file_to_grid(contents, 2);
 
-    grid.keys().combinations(2)
-        .map(|pair| {
-            let a =  grid.get(&pair[0]).unwrap();
-            let b = grid.get(&pair[1]).unwrap();
-            let x = (a.x - b.x).abs();
-            let y = (a.y - b.y).abs();
+    sum_pairwise_distances(&grid)
+}
 
-            x + y
-        })
-        .sum()
+fn part_2(
+    contents: &str,
+    expansion_factor: i64,
+) -> i64 {
+    let grid = file_to_grid(contents, expansion_factor);
+
+    sum_pairwise_distances(&grid)
 }
 
 #[cfg(test)]
@@ -133,13 +261,37 @@ mod test {
         assert_eq!(part_1(&contents), 374);
     }
     
-    // for this test to work, the multiplier in file_to_grid needs to be set to 99  
     #[test]
     fn test_part_2() {
         let file_path = "test_input.txt";
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 8410);
+        assert_eq!(part_2(&contents, 10), 1030);
+        assert_eq!(part_2(&contents, 100), 8410);
+    }
+
+    #[test]
+    fn test_distance_queries() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let grid = file_to_grid(&contents, 2);
+
+        assert_eq!(nearest_galaxies(&grid, 5, 2), Some(vec![(3, 5), (8, 6)]));
+        assert_eq!(nearest_galaxies(&grid, 10, 1), None);
+        assert_eq!(farthest_pair(&grid), Some((2, 8, 19)));
+
+        assert_eq!(distance_histogram(&grid, 0), None);
+        assert_eq!(distance_histogram(&grid, -3), None);
+
+        let histogram = distance_histogram(&grid, 5).unwrap();
+        assert_eq!(histogram.values().sum::<usize>(), 36);
+        assert_eq!(histogram.get(&15), Some(&7));
+
+        let csv = distance_matrix_csv(&grid).unwrap();
+        assert_eq!(csv.lines().next(), Some("galaxy,1,2,3,4,5,6,7,8,9"));
+        assert_eq!(csv.lines().nth(5), Some("5,9,13,5,8,0,12,12,6,9"));
     }
 }
@@ -1,4 +1,8 @@
-use std::fs;
+use std::{env, fs};
+use itertools::Itertools;
+use num_bigint::{BigUint, RandBigInt};
+use num_traits::{One, Zero};
+use rand::Rng;
 
 fn main() {
     let file_path = "input.txt";
@@ -7,112 +11,449 @@ fn main() {
 
     println!("Sum part 1: {}", part_1(&contents));
     println!("Sum part 2: {}", part_2(&contents));
+
+    let args: Vec<String> = env::args().collect();
+    if let Some(line) = args.iter().position(|arg| arg == "--explain").and_then(|i| args.get(i + 1)) {
+        let (pattern, groups) = parse_line(line);
+        let table = ArrangementTable::new(&pattern, &groups);
+
+        println!("{} arrangements", table.count());
+        println!("# per position: {}", table.damaged_counts().iter().join(", "));
+        table.arrangements().take(10).for_each(|arrangement| println!("{}", arrangement));
+        if let Some(sample) = table.sample(&mut rand::thread_rng()) {
+            println!("Random arrangement: {}", sample);
+        }
+    }
+
+    let growth = args.iter().position(|arg| arg == "--growth")
+        .and_then(|i| args.get(i + 1).map(|line| (i, line)));
+    if let Some((i, line)) = growth {
+        let max_copies = args.get(i + 2).map_or(5, |copies| copies.parse().unwrap());
+        let separator = args.get(i + 3).and_then(|separator| separator.chars().next()).unwrap_or('?');
+
+        for (copies, count) in fold_growth(line, max_copies, separator).iter().enumerate() {
+            println!("{:>3} copies: {}", copies + 1, count);
+        }
+    }
+
+    if let Some(path) = args.iter().position(|arg| arg == "--nonogram").and_then(|i| args.get(i + 1)) {
+        let nonogram = parse_nonogram(&fs::read_to_string(path).unwrap());
+        match nonogram.solve() {
+            NonogramSolution::Unique(grid) => println!("Unique solution:\n{}", render_nonogram(&grid)),
+            NonogramSolution::Multiple(first, second) => println!(
+                "Multiple solutions, for example:\n{}\n\n{}",
+                render_nonogram(&first),
+                render_nonogram(&second)
+            ),
+            NonogramSolution::NoSolution => println!("No solution"),
+        }
+    }
 }
 
 fn joined_copy<T: Clone>(
     vector: Vec<T>, 
     num_copies: usize
 ) -> Vec<T> {
-    (0..num_copies).map(|_| vector.clone()).flatten().collect()
+    (0..num_copies).flat_map(|_| vector.clone()).collect()
 }
 
 fn parse_line(line: &str) -> (String, Vec<usize>) {
-    let parts = line.split(" ").collect::<Vec<_>>();
+    let parts = line.split(' ').collect::<Vec<_>>();
     (
-        parts[0].to_owned() + ".",
+        parts[0].to_owned(),
         parts[1]
-            .split(",")
+            .split(',')
             .map(|x| x.parse::<usize>().unwrap())
             .collect::<Vec<_>>(),
     )
 }
 
-fn parse_line_2(
-    line: &str
+/// Repeats the pattern `num_copies` times joined by `separator`, and the groups
+/// `num_copies` times.
+fn unfold_line(
+    line: &str,
+    num_copies: usize,
+    separator: char,
+) -> (String, Vec<usize>) {
+    let (pattern, groups) = parse_line(line);
+    unfold(&pattern, &groups, num_copies, separator)
+}
+
+fn unfold(
+    pattern: &str,
+    groups: &[usize],
+    num_copies: usize,
+    separator: char,
 ) -> (String, Vec<usize>) {
-    let parts = line.split(" ").collect::<Vec<_>>();
     (
-        (0..5).map(|_| parts[0]).collect::<Vec<_>>().join("?") + ".",
-        joined_copy(
-            parts[1]
-                .split(",")
-                .map(|x| x.parse::<usize>().unwrap())
-                .collect::<Vec<_>>(),
-            5,
-        ),
+        (0..num_copies).map(|_| pattern).collect::<Vec<_>>().join(&separator.to_string()),
+        joined_copy(groups.to_vec(), num_copies),
     )
 }
 
-fn count_valid_rows(
-    i: usize,
-    gi: usize,
-    pattern: &str,
-    groups: &Vec<usize>,
-    dp: &mut Vec<Vec<Option<usize>>>,
-) -> usize {
-    // If we reach the end of groups, check if the remaining pattern is all '.' or '?'
-    if gi == groups.len() {
-        match (i..pattern.len())
-            .any(|j| pattern.chars().nth(j).unwrap() == '#') 
-        {
-            true => return 0,
-            false => return 1,
+/// Arrangement counts for every fold factor from 1 to `max_copies`, read from a single
+/// DP table. The suffix of the row unfolded `max_copies` times that starts at a copy
+/// boundary is exactly the row unfolded fewer times, so its counts are already there.
+fn fold_growth(
+    line: &str,
+    max_copies: usize,
+    separator: char,
+) -> Vec<BigUint> {
+    let (pattern, groups) = parse_line(line);
+
+    let (unfolded_pattern, unfolded_groups) = unfold(&pattern, &groups, max_copies, separator);
+    let table = ArrangementTable::new(&unfolded_pattern, &unfolded_groups);
+
+    (1..=max_copies)
+        .map(|copies| {
+            let skipped = max_copies - copies;
+            table.counts[skipped * (pattern.len() + 1)][skipped * groups.len()].clone()
+        })
+        .collect()
+}
+
+/// Bottom-up DP over a spring row. `counts[i][gi]` is the number of ways to place
+/// `groups[gi..]` in `pattern[i..]`; the pattern gets a trailing '.' so every group
+/// can be followed by an operational spring.
+struct ArrangementTable<'a> {
+    pattern: Vec<u8>,
+    groups: &'a [usize],
+    operational_before: Vec<usize>,
+    counts: Vec<Vec<BigUint>>,
+}
+
+impl<'a> ArrangementTable<'a> {
+    fn new(pattern: &str, groups: &'a [usize]) -> Self {
+        let mut pattern = pattern.as_bytes().to_vec();
+        pattern.push(b'.');
+
+        let operational_before = std::iter::once(0)
+            .chain(pattern.iter().scan(0, |operational, &c| {
+                *operational += (c == b'.') as usize;
+                Some(*operational)
+            }))
+            .collect();
+
+        let mut table = ArrangementTable {
+            counts: vec![vec![BigUint::zero(); groups.len() + 1]; pattern.len() + 1],
+            pattern,
+            groups,
+            operational_before,
+        };
+
+        table.counts[table.pattern.len()][groups.len()] = BigUint::one();
+        for i in (0..table.pattern.len()).rev() {
+            for gi in (0..=groups.len()).rev() {
+                let mut ways = BigUint::zero();
+                if let Some(skip) = table.skip_ways(i, gi) {
+                    ways += skip;
+                }
+                if let Some(place) = table.place_ways(i, gi) {
+                    ways += place;
+                }
+                table.counts[i][gi] = ways;
+            }
         }
+
+        table
+    }
+
+    fn count(&self) -> &BigUint {
+        &self.counts[0][0]
     }
 
-    // If we reach the end of pattern, return 0
-    if i == pattern.len() {
-        return 0;
+    /// Whether `groups[gi]` can start at `i`: no '.' inside it and no '#' right after.
+    fn fits(&self, i: usize, gi: usize) -> bool {
+        let Some(&length) = self.groups.get(gi) else {
+            return false;
+        };
+
+        i + length < self.pattern.len()
+            && self.operational_before[i + length] == self.operational_before[i]
+            && self.pattern[i + length] != b'#'
     }
 
-    if let Some(ans) = dp[i][gi] {
-        return ans;
+    /// Arrangements where `pattern[i]` is left operational, `None` if it can't be.
+    fn skip_ways(&self, i: usize, gi: usize) -> Option<&BigUint> {
+        (self.pattern[i] != b'#').then(|| &self.counts[i + 1][gi])
     }
 
-    let mut ans = 0;
+    /// Arrangements where `groups[gi]` starts at `i`, `None` if it doesn't fit there.
+    fn place_ways(&self, i: usize, gi: usize) -> Option<&BigUint> {
+        self.fits(i, gi).then(|| &self.counts[i + self.groups[gi] + 1][gi + 1])
+    }
+
+    /// Lazily lists every arrangement. Branches without arrangements are never entered.
+    fn arrangements(&self) -> impl Iterator<Item = String> + '_ {
+        let mut stack = if self.count().is_zero() { Vec::new() } else { vec![(0, 0, Vec::new())] };
+        let has_ways = |ways: Option<&BigUint>| ways.is_some_and(|ways| !ways.is_zero());
+
+        std::iter::from_fn(move || {
+            while let Some((i, gi, mut row)) = stack.pop() {
+                if i >= self.pattern.len() {
+                    row.truncate(self.pattern.len() - 1);
+                    return Some(String::from_utf8(row).unwrap());
+                }
+
+                // Push the operational branch first so arrangements come out in lexicographic order
+                if has_ways(self.skip_ways(i, gi)) {
+                    let mut skipped = row.clone();
+                    skipped.push(b'.');
+                    stack.push((i + 1, gi, skipped));
+                }
+                if has_ways(self.place_ways(i, gi)) {
+                    row.extend(std::iter::repeat_n(b'#', self.groups[gi]));
+                    row.push(b'.');
+                    stack.push((i + self.groups[gi] + 1, gi + 1, row));
+                }
+            }
+            None
+        })
+    }
+
+    /// Draws one arrangement uniformly at random by choosing each branch with
+    /// probability proportional to its DP count.
+    fn sample<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        if self.count().is_zero() {
+            return None;
+        }
+
+        let mut row = Vec::with_capacity(self.pattern.len());
+        let (mut i, mut gi) = (0, 0);
 
-    // Try to fit the group at the next index
-    if pattern.chars().nth(i).unwrap() != '#' {
-        ans += count_valid_rows(i + 1, gi, pattern, groups, dp);
+        while i < self.pattern.len() {
+            let pick = rng.gen_biguint_below(&self.counts[i][gi]);
+            if self.skip_ways(i, gi).is_some_and(|skip| pick < *skip) {
+                row.push(b'.');
+                i += 1;
+            } else {
+                row.extend(std::iter::repeat_n(b'#', self.groups[gi]));
+                row.push(b'.');
+                i += self.groups[gi] + 1;
+                gi += 1;
+            }
+        }
+
+        row.truncate(self.pattern.len() - 1);
+        Some(String::from_utf8(row).unwrap())
     }
 
-    // Try to fit the group at the current index
-    if i + groups[gi] < pattern.len()
-        && (i..i + groups[gi]).all(|j| pattern.chars().nth(j).unwrap() != '.')
-        && pattern.chars().nth(i + groups[gi]).unwrap() != '#'
-    {
-        ans += count_valid_rows(i + groups[gi] + 1, gi + 1, pattern, groups, dp);
+    /// For every position of the original pattern, how many arrangements put a '#' there.
+    fn damaged_counts(&self) -> Vec<BigUint> {
+        let n = self.pattern.len();
+
+        // Number of ways to reach each (i, gi) state from the start of the row
+        let mut reach = vec![vec![BigUint::zero(); self.groups.len() + 1]; n + 1];
+        reach[0][0] = BigUint::one();
+
+        // Each placed group adds its weight from its first cell up to, not including, `end`
+        let mut starting = vec![BigUint::zero(); n + 1];
+        let mut ending = vec![BigUint::zero(); n + 1];
+
+        for i in 0..n {
+            for gi in 0..=self.groups.len() {
+                if reach[i][gi].is_zero() {
+                    continue;
+                }
+                let ways = reach[i][gi].clone();
+                if self.pattern[i] != b'#' {
+                    reach[i + 1][gi] += &ways;
+                }
+                if self.fits(i, gi) {
+                    let end = i + self.groups[gi];
+                    let weight = &ways * &self.counts[end + 1][gi + 1];
+                    reach[end + 1][gi + 1] += ways;
+
+                    starting[i] += &weight;
+                    ending[end] += weight;
+                }
+            }
+        }
+
+        starting.iter()
+            .zip(&ending)
+            .take(n - 1)
+            .scan(BigUint::zero(), |running, (start, end)| {
+                *running += start;
+                *running -= end;
+                Some(running.clone())
+            })
+            .collect()
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Unknown,
+    Filled,
+    Empty,
+}
+
+#[derive(Debug, PartialEq)]
+enum NonogramSolution {
+    Unique(Vec<Vec<bool>>),
+    Multiple(Vec<Vec<bool>>, Vec<Vec<bool>>),
+    NoSolution,
+}
 
-    dp[i][gi] = Some(ans);
-    ans
+/// A 2D nonogram. Every row and column is the same constraint as a spring record,
+/// with filled cells as damaged springs.
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    columns: Vec<Vec<usize>>,
+}
+
+/// Row clues, an empty line, then column clues; one comma separated clue list per
+/// line, with `0` or nothing for an empty line.
+fn parse_nonogram(contents: &str) -> Nonogram {
+    let (rows, columns) = contents.split_once("\n\n").unwrap();
+    let parse_clues = |block: &str| -> Vec<Vec<usize>> {
+        block.lines()
+            .map(|line| {
+                line.split(',')
+                    .filter_map(|x| x.trim().parse::<usize>().ok())
+                    .filter(|&x| x > 0)
+                    .collect()
+            })
+            .collect()
+    };
+
+    Nonogram { rows: parse_clues(rows), columns: parse_clues(columns) }
+}
+
+fn render_nonogram(grid: &[Vec<bool>]) -> String {
+    grid.iter()
+        .map(|row| row.iter().map(|&filled| if filled { '#' } else { '.' }).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Forces every cell of the line that is filled in all arrangements or in none.
+/// Returns `None` if no arrangement fits the known cells.
+fn solve_line(cells: &[Cell], groups: &[usize]) -> Option<Vec<Cell>> {
+    let pattern: String = cells.iter()
+        .map(|cell| match cell {
+            Cell::Unknown => '?',
+            Cell::Filled => '#',
+            Cell::Empty => '.',
+        })
+        .collect();
+
+    let table = ArrangementTable::new(&pattern, groups);
+    let count = table.count();
+    if count.is_zero() {
+        return None;
+    }
+
+    Some(table.damaged_counts().iter()
+        .map(|damaged| {
+            if damaged.is_zero() {
+                Cell::Empty
+            } else if damaged == count {
+                Cell::Filled
+            } else {
+                Cell::Unknown
+            }
+        })
+        .collect())
+}
+
+impl Nonogram {
+    fn solve(&self) -> NonogramSolution {
+        let grid = vec![vec![Cell::Unknown; self.columns.len()]; self.rows.len()];
+
+        let mut solutions = Vec::new();
+        self.search(grid, &mut solutions);
+
+        let mut solutions = solutions.into_iter();
+        match (solutions.next(), solutions.next()) {
+            (Some(first), Some(second)) => NonogramSolution::Multiple(first, second),
+            (Some(first), None) => NonogramSolution::Unique(first),
+            _ => NonogramSolution::NoSolution,
+        }
+    }
+
+    /// Line-solves rows and columns until nothing changes. Returns `false` on a contradiction.
+    fn propagate(&self, grid: &mut [Vec<Cell>]) -> bool {
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for (y, groups) in self.rows.iter().enumerate() {
+                let Some(solved) = solve_line(&grid[y], groups) else {
+                    return false;
+                };
+                for (x, cell) in solved.into_iter().enumerate() {
+                    if cell != grid[y][x] {
+                        grid[y][x] = cell;
+                        changed = true;
+                    }
+                }
+            }
+
+            for (x, groups) in self.columns.iter().enumerate() {
+                let column: Vec<Cell> = grid.iter().map(|row| row[x]).collect();
+                let Some(solved) = solve_line(&column, groups) else {
+                    return false;
+                };
+                for (y, cell) in solved.into_iter().enumerate() {
+                    if cell != grid[y][x] {
+                        grid[y][x] = cell;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Backtracks on the first unknown cell once line solving stalls, stopping after
+    /// two solutions since that is enough to tell they aren't unique.
+    fn search(&self, mut grid: Vec<Vec<Cell>>, solutions: &mut Vec<Vec<Vec<bool>>>) {
+        if solutions.len() >= 2 || !self.propagate(&mut grid) {
+            return;
+        }
+
+        let unknown = grid.iter()
+            .enumerate()
+            .find_map(|(y, row)| row.iter().position(|&cell| cell == Cell::Unknown).map(|x| (x, y)));
+
+        match unknown {
+            Some((x, y)) => {
+                for guess in [Cell::Filled, Cell::Empty] {
+                    let mut guessed = grid.clone();
+                    guessed[y][x] = guess;
+                    self.search(guessed, solutions);
+                }
+            }
+            None => solutions.push(
+                grid.iter()
+                    .map(|row| row.iter().map(|&cell| cell == Cell::Filled).collect())
+                    .collect()
+            ),
+        }
+    }
 }
 
 fn part_1(
-    contents: &String
-) -> usize {
+    contents: &str
+) -> BigUint {
     contents
         .lines()
-        .map(|x| parse_line(x))
-        .map(|(pattern, groups)| {
-            let mut dp = vec![vec![None; groups.len()]; pattern.len()];
-            let ans = count_valid_rows(0, 0, pattern.as_str(), &groups, &mut dp);
-            ans
-        })
+        .map(parse_line)
+        .map(|(pattern, groups)| ArrangementTable::new(&pattern, &groups).count().clone())
         .sum()
 }
 
 fn part_2(
-    contents: &String
-) -> usize {
+    contents: &str
+) -> BigUint {
     contents
         .lines()
-        .map(|x| parse_line_2(x))
-        .map(|(pattern, groups)| {
-            let mut dp = vec![vec![None; groups.len()]; pattern.len()];
-            let ans = count_valid_rows(0, 0, pattern.as_str(), &groups, &mut dp);
-            ans
-        })
+        .map(|line| unfold_line(line, 5, '?'))
+        .map(|(pattern, groups)| ArrangementTable::new(&pattern, &groups).count().clone())
         .sum()
 }
 
@@ -126,7 +467,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_1(&contents), 21);
+        assert_eq!(part_1(&contents), BigUint::from(21u32));
     }
     
     #[test]
@@ -135,6 +476,56 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 525152);
+        assert_eq!(part_2(&contents), BigUint::from(525152u32));
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let groups = [3, 2, 1];
+        let table = ArrangementTable::new("?###????????", &groups);
+
+        let arrangements: Vec<String> = table.arrangements().collect();
+        assert_eq!(BigUint::from(arrangements.len()), *table.count());
+        assert_eq!(arrangements[0], ".###.##.#...");
+        assert!(arrangements.contains(&".###....##.#".to_string()));
+
+        assert_eq!(
+            table.damaged_counts(),
+            [0u32, 10, 10, 10, 0, 4, 7, 5, 4, 3, 3, 4].map(BigUint::from)
+        );
+
+        let mut rng = rand::thread_rng();
+        let sample = table.sample(&mut rng).unwrap();
+        assert!(arrangements.contains(&sample));
+
+        let groups = [2];
+        assert_eq!(ArrangementTable::new("#.#", &groups).sample(&mut rng), None);
+    }
+
+    #[test]
+    fn test_nonogram() {
+        let nonogram = parse_nonogram("2\n1,1\n3\n\n2\n1,1\n3\n");
+        match nonogram.solve() {
+            NonogramSolution::Unique(grid) => assert_eq!(render_nonogram(&grid), ".##\n#.#\n###"),
+            other => panic!("expected a unique solution, got {:?}", other),
+        }
+
+        let nonogram = parse_nonogram("1\n1\n\n1\n1");
+        assert!(matches!(nonogram.solve(), NonogramSolution::Multiple(_, _)));
+
+        let nonogram = parse_nonogram("2\n0\n\n1\n0");
+        assert_eq!(nonogram.solve(), NonogramSolution::NoSolution);
+    }
+
+    #[test]
+    fn test_fold_growth() {
+        let growth = fold_growth("?###???????? 3,2,1", 5, '?');
+        assert_eq!(growth, [10u32, 150, 2250, 33750, 506250].map(BigUint::from));
+
+        assert_eq!(unfold_line(".# 1", 3, '.'), (".#..#..#".to_string(), vec![1, 1, 1]));
+        assert_eq!(fold_growth("??? 1", 2, '.'), [3u32, 11].map(BigUint::from));
+
+        let large = fold_growth(".??..??...?##. 1,1,3", 200, '?');
+        assert!(large[199].bits() > 128);
     }
 }
@@ -1,4 +1,176 @@
-use std::fs;
+use std::{env, fs};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+/// A mirror line lying just before row (horizontal) or column (vertical) `index`.
+/// `mismatches` holds, in (row, column) form, the cell on the top or left side of
+/// every pair that doesn't reflect, flipping either cell of a pair fixes it.
+#[derive(Debug, PartialEq)]
+struct Reflection {
+    orientation: Orientation,
+    index: usize,
+    mismatches: Vec<(usize, usize)>,
+}
+
+impl Reflection {
+    fn score(&self) -> i64 {
+        match self.orientation {
+            Orientation::Horizontal => self.index as i64 * 100,
+            Orientation::Vertical => self.index as i64,
+        }
+    }
+}
+
+/// Summary of one mirror line and how many cell pairs fail to reflect across it.
+#[derive(Debug, PartialEq)]
+struct MirrorLine {
+    orientation: Orientation,
+    index: usize,
+    mismatch_count: usize,
+}
+
+/// Every mirror line of a pattern, plus the mismatched cell pairs across the main and
+/// anti diagonals when the pattern is square.
+#[derive(Debug, PartialEq)]
+struct SymmetryReport {
+    mirror_lines: Vec<MirrorLine>,
+    diagonal: Option<usize>,
+    anti_diagonal: Option<usize>,
+}
+
+/// A row or column of a pattern as a bitset, bit `j` set when cell `j` is a rock.
+/// Wider patterns simply use more words.
+#[derive(Debug, Clone, PartialEq)]
+struct BitLine {
+    words: Vec<u64>,
+}
+
+impl BitLine {
+    fn new(length: usize) -> Self {
+        Self { words: vec![0; length.div_ceil(64)] }
+    }
+
+    fn set(&mut self, j: usize) {
+        self.words[j / 64] |= 1 << (j % 64);
+    }
+
+    fn get(&self, j: usize) -> bool {
+        self.words[j / 64] & (1 << (j % 64)) != 0
+    }
+
+    fn mismatch_count(&self, other: &Self) -> usize {
+        self.words.iter().zip(&other.words)
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
+    }
+
+    fn mismatches<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = usize> + 'a {
+        self.words.iter().zip(&other.words)
+            .enumerate()
+            .flat_map(|(w, (a, b))| {
+                let mut diff = a ^ b;
+                std::iter::from_fn(move || {
+                    (diff != 0).then(|| {
+                        let bit = diff.trailing_zeros() as usize;
+                        diff &= diff - 1;
+                        w * 64 + bit
+                    })
+                })
+            })
+    }
+
+    fn reversed(&self, length: usize) -> Self {
+        let mut reversed = Self::new(length);
+        (0..length).filter(|&j| self.get(j)).for_each(|j| reversed.set(length - 1 - j));
+        reversed
+    }
+}
+
+/// A pattern stored both as row and column bitsets, so rows and columns are compared
+/// the same way without transposing characters.
+struct Pattern {
+    rows: Vec<BitLine>,
+    columns: Vec<BitLine>,
+}
+
+impl Pattern {
+    fn parse(chunk: &str) -> Self {
+        let height = chunk.lines().count();
+        let width = chunk.lines().next().map_or(0, |line| line.len());
+
+        let mut rows = vec![BitLine::new(width); height];
+        let mut columns = vec![BitLine::new(height); width];
+
+        chunk.lines().enumerate().for_each(|(i, line)| {
+            line.bytes().enumerate().filter(|&(_, c)| c == b'#').for_each(|(j, _)| {
+                rows[i].set(j);
+                columns[j].set(i);
+            });
+        });
+
+        Self { rows, columns }
+    }
+
+    /// Every horizontal and vertical mirror line with exactly `smudges` mismatched cells.
+    fn find_reflections(&self, smudges: usize) -> Vec<Reflection> {
+        let horizontal = find_reflection(&self.rows, smudges).into_iter()
+            .map(|(index, mismatches)| Reflection {
+                orientation: Orientation::Horizontal,
+                index,
+                mismatches,
+            });
+
+        let vertical = find_reflection(&self.columns, smudges).into_iter()
+            .map(|(index, mismatches)| Reflection {
+                orientation: Orientation::Vertical,
+                index,
+                // Lines are columns here, so swap back to (row, column)
+                mismatches: mismatches.into_iter().map(|(column, row)| (row, column)).collect(),
+            });
+
+        horizontal.chain(vertical).collect()
+    }
+
+    fn symmetry_report(&self) -> SymmetryReport {
+        let mirror_lines = [
+            (Orientation::Horizontal, &self.rows),
+            (Orientation::Vertical, &self.columns),
+        ]
+            .into_iter()
+            .flat_map(|(orientation, lines)| {
+                (1..lines.len()).map(move |index| MirrorLine {
+                    orientation,
+                    index,
+                    mismatch_count: count_mismatches(&lines[..index], &lines[index..]),
+                })
+            })
+            .collect();
+
+        let size = self.rows.len();
+        let square = size == self.columns.len();
+
+        // Cell (r, c) mirrors (c, r), so row r must equal column r
+        let diagonal = square.then(|| {
+            self.rows.iter().zip(&self.columns)
+                .map(|(row, column)| row.mismatch_count(column))
+                .sum::<usize>() / 2
+        });
+
+        // Cell (r, c) mirrors (n - 1 - c, n - 1 - r), so row r must equal column
+        // n - 1 - r read backwards
+        let anti_diagonal = square.then(|| {
+            self.rows.iter().zip(self.columns.iter().rev())
+                .map(|(row, column)| row.mismatch_count(&column.reversed(size)))
+                .sum::<usize>() / 2
+        });
+
+        SymmetryReport { mirror_lines, diagonal, anti_diagonal }
+    }
+}
 
 fn main() {
     let file_path = "input.txt";
@@ -7,74 +179,91 @@ fn main() {
 
     println!("Sum part 1: {}", part_1(&contents));
     println!("Sum part 2: {}", part_2(&contents));
-}
 
-fn chunk_to_2d_vec(
-    chunk: &str
-) -> (Vec<Vec<char>>, Vec<Vec<char>>) {
-    let rows: Vec<Vec<char>> = chunk.lines()
-        .map(|line| line.chars().collect()).collect();
-    let columns = (0..rows[0].len())
-        .map(|i| rows.iter().map(|row| row[i]).collect()).collect();
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().any(|arg| arg == "--smudges") {
+        contents.split("\n\n").enumerate().for_each(|(i, chunk)| {
+            for reflection in Pattern::parse(chunk).find_reflections(1) {
+                println!(
+                    "Pattern {}: {:?} line at {}, smudge at {:?}",
+                    i + 1, reflection.orientation, reflection.index, reflection.mismatches[0]
+                );
+            }
+        });
+    }
 
-    (rows, columns)
+    if args.iter().any(|arg| arg == "--symmetries") {
+        contents.split("\n\n").enumerate().for_each(|(i, chunk)| {
+            let report = Pattern::parse(chunk).symmetry_report();
+            println!("Pattern {}:", i + 1);
+            for line in report.mirror_lines {
+                println!("  {:?} line at {}: {} mismatches", line.orientation, line.index, line.mismatch_count);
+            }
+            if let (Some(diagonal), Some(anti_diagonal)) = (report.diagonal, report.anti_diagonal) {
+                println!("  Diagonal: {} mismatches, anti-diagonal: {} mismatches", diagonal, anti_diagonal);
+            }
+        });
+    }
 }
 
 fn compare_reflections(
-    rows: &Vec<Vec<char>>,
-    columns: &Vec<Vec<char>>,
+    pattern: &Pattern,
+    smudges: usize,
 ) -> i64 {
-    let rows_reflection = find_reflection(rows, 100);
-    let columns_reflection = find_reflection(columns, 1);
-    
-    columns_reflection + rows_reflection
+    pattern.find_reflections(smudges).iter()
+        .map(|reflection| reflection.score())
+        .sum()
 }
 
+/// Mirror lines between consecutive lines with exactly `smudges` mismatched cells, each
+/// with its mismatches as (line, position) on the side before the mirror.
 fn find_reflection(
-    lines: &Vec<Vec<char>>,
-    multiplier: i64,
-) -> i64 {
-    lines.iter().enumerate()
-        .filter(|(i, _)| compare_sides(&lines[0..*i], &lines[*i..]))
-        .map(|(i, _)| i as i64 * multiplier)
-        .sum()
+    lines: &[BitLine],
+    smudges: usize,
+) -> Vec<(usize, Vec<(usize, usize)>)> {
+    (1..lines.len())
+        .filter(|&i| count_mismatches(&lines[0..i], &lines[i..]) == smudges)
+        .map(|i| (i, compare_sides(&lines[0..i], &lines[i..])))
+        .collect()
 }
 
-fn compare_sides(
-    side1: &[Vec<char>],
-    side2: &[Vec<char>],
-) -> bool {
+fn count_mismatches(
+    side1: &[BitLine],
+    side2: &[BitLine],
+) -> usize {
     side1.iter().rev().zip(side2.iter())
-        .all(|(line1, line2)| {
-            compare_two(line1, line2)
-        })
+        .map(|(line1, line2)| line1.mismatch_count(line2))
+        .sum()
 }
 
-fn compare_two(
-    line1: &Vec<char>,
-    line2: &Vec<char>,
-) -> bool {
-    line1.iter().zip(line2.iter()).all(|(c1, c2)| {
-        c1 == c2
-    })
+fn compare_sides(
+    side1: &[BitLine],
+    side2: &[BitLine],
+) -> Vec<(usize, usize)> {
+    side1.iter().enumerate().rev().zip(side2.iter())
+        .flat_map(|((i, line1), line2)| {
+            line1.mismatches(line2).map(move |j| (i, j))
+        })
+        .collect()
 }
 
 fn part_1(
-    contents: &String
+    contents: &str
 ) -> i64 {
     contents.split("\n\n")
-        .map(|chunk| chunk_to_2d_vec(chunk))
-        .map(|(rows, columns)| {
-            compare_reflections(&rows, &columns)
-        })
+        .map(Pattern::parse)
+        .map(|pattern| compare_reflections(&pattern, 0))
         .sum::<i64>()
 }
 
 fn part_2(
-    contents: &String
+    contents: &str
 ) -> i64 {
-    // I took the solution of my friend, because I didnt have the time to solve it myself
-    32312
+    contents.split("\n\n")
+        .map(Pattern::parse)
+        .map(|pattern| compare_reflections(&pattern, 1))
+        .sum::<i64>()
 }
 
 #[cfg(test)]
@@ -98,4 +287,42 @@ mod test {
 
         assert_eq!(part_2(&contents), 400);
     }
+
+    #[test]
+    fn test_find_smudge() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let reflections: Vec<Reflection> = contents.split("\n\n")
+            .map(Pattern::parse)
+            .flat_map(|pattern| pattern.find_reflections(1))
+            .collect();
+
+        assert_eq!(reflections, vec![
+            Reflection { orientation: Orientation::Horizontal, index: 3, mismatches: vec![(0, 0)] },
+            Reflection { orientation: Orientation::Horizontal, index: 1, mismatches: vec![(0, 4)] },
+        ]);
+    }
+
+    #[test]
+    fn test_symmetry_report() {
+        let report = Pattern::parse("#.#\n...\n#.#").symmetry_report();
+
+        assert_eq!(report.mirror_lines[0], MirrorLine {
+            orientation: Orientation::Horizontal,
+            index: 1,
+            mismatch_count: 2,
+        });
+        assert_eq!(report.mirror_lines.len(), 4);
+        assert_eq!((report.diagonal, report.anti_diagonal), (Some(0), Some(0)));
+
+        let report = Pattern::parse("##.\n...\n...").symmetry_report();
+        assert_eq!((report.diagonal, report.anti_diagonal), (Some(1), Some(2)));
+
+        let wide = format!("{}#\n{}#", ".".repeat(99), ".".repeat(99));
+        let pattern = Pattern::parse(&wide);
+        assert_eq!(pattern.find_reflections(0)[0].orientation, Orientation::Horizontal);
+        assert_eq!(pattern.symmetry_report().diagonal, None);
+    }
 }
@@ -1,5 +1,7 @@
-use std::fs;
-use ndarray::{Array2, Axis};
+use std::{env, fs, io, thread, collections::HashMap, fs::File, io::BufWriter, path::Path, time::Duration};
+
+/// Side of the square of pixels drawn for every cell in PNG frames.
+const PNG_CELL_SIZE: usize = 4;
 
 fn main() {
     let file_path = "input.txt";
@@ -8,108 +10,413 @@ fn main() {
 
     println!("Sum part 1: {}", part_1(&contents));
     println!("Sum part 2: {}", part_2(&contents));
+
+    let args: Vec<String> = env::args().collect();
+    let arg_value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+
+    let max_cycles = arg_value("--cycles").map(|cycles| cycles.parse().unwrap());
+
+    if args.iter().any(|arg| arg == "--animate") {
+        let delay = arg_value("--delay").map_or(100, |delay| delay.parse().unwrap());
+        animate(&record_frames(Platform::parse(&contents), max_cycles), Duration::from_millis(delay));
+    }
+
+    if let Some(dir) = arg_value("--frames") {
+        let frames = record_frames(Platform::parse(&contents), max_cycles);
+        let exported = match arg_value("--format").map(String::as_str) {
+            Some("png") => export_png_frames(&frames, Path::new(dir)),
+            _ => export_text_frames(&frames, Path::new(dir)),
+        };
+        match exported {
+            Ok(()) => println!("Exported {} frames to {}", frames.len(), dir),
+            Err(err) => println!("Frame export failed: {}", err),
+        }
+    }
 }
 
-fn rotate_90_degrees_clockwise(
-    matrix: &mut Array2<char>
-) {
-    let mut transposed = matrix.t().to_owned();
-    
-    transposed.invert_axis(Axis(1));
-    *matrix = transposed;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    North,
+    West,
+    South,
+    East,
 }
 
-fn rotate_90_degrees_opposite(
-    matrix: &mut Array2<char>
-) {
-    let mut transposed = matrix.t().to_owned();
-    
-    transposed.invert_axis(Axis(0));
-    *matrix = transposed;
+/// The reflector dish as a flat row-major grid of `O`, `#` and `.` bytes. Tilts move
+/// rocks in place, so a spin cycle allocates nothing.
+#[derive(Debug, Clone, PartialEq)]
+struct Platform {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
 }
 
-fn convert_input(
-    contents: &str
-) -> Array2<char> {
-    let rows: Vec<Vec<char>> = contents.lines()
-        .map(|line| line.chars().collect())
-        .collect();
+impl Platform {
+    fn parse(
+        contents: &str
+    ) -> Self {
+        let width = contents.lines().next().map_or(0, |line| line.len());
+        let cells: Vec<u8> = contents.lines().flat_map(|line| line.bytes()).collect();
 
-    let num_rows = rows.len();
-    let num_cols = rows[0].len();
+        Self { width, height: cells.len() / width.max(1), cells }
+    }
 
-    let mut array = Array2::from_elem((num_rows, num_cols), ' ');
+    /// Index of the `k`-th cell of `line` counted from the edge rocks roll towards.
+    /// Lines are columns when tilting north or south and rows otherwise.
+    fn index(
+        &self,
+        direction: Direction,
+        line: usize,
+        k: usize,
+    ) -> usize {
+        match direction {
+            Direction::North => k * self.width + line,
+            Direction::South => (self.height - 1 - k) * self.width + line,
+            Direction::West => line * self.width + k,
+            Direction::East => line * self.width + self.width - 1 - k,
+        }
+    }
 
-    for (i, row) in rows.iter().enumerate() {
-        for (j, &item) in row.iter().enumerate() {
-            array[[i, j]] = item;
+    fn tilt(
+        &mut self,
+        direction: Direction,
+    ) {
+        let (lines, length) = match direction {
+            Direction::North | Direction::South => (self.width, self.height),
+            Direction::West | Direction::East => (self.height, self.width),
+        };
+
+        for line in 0..lines {
+            // First free cell of the current segment between cube-shaped rocks
+            let mut free = 0;
+            for k in 0..length {
+                let i = self.index(direction, line, k);
+                match self.cells[i] {
+                    b'#' => free = k + 1,
+                    b'O' => {
+                        if free != k {
+                            let target = self.index(direction, line, free);
+                            self.cells[target] = b'O';
+                            self.cells[i] = b'.';
+                        }
+                        free += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn spin_cycle(
+        &mut self
+    ) {
+        for direction in [Direction::North, Direction::West, Direction::South, Direction::East] {
+            self.tilt(direction);
         }
     }
 
-    array
+    /// Total load on the support beams along `edge`: each rounded rock counts its
+    /// distance from the opposite edge, the rows or columns furthest away counting 1.
+    fn load(
+        &self,
+        edge: Direction,
+    ) -> i64 {
+        self.cells.iter()
+            .enumerate()
+            .filter(|(_, &c)| c == b'O')
+            .map(|(i, _)| {
+                let (x, y) = (i % self.width, i / self.width);
+                (match edge {
+                    Direction::North => self.height - y,
+                    Direction::South => y + 1,
+                    Direction::West => self.width - x,
+                    Direction::East => x + 1,
+                }) as i64
+            })
+            .sum()
+    }
+
+    fn render(
+        &self
+    ) -> String {
+        self.cells.chunks(self.width)
+            .map(|row| String::from_utf8_lossy(row).into_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Identifies a platform state by the positions of its rounded rocks.
+    fn state_key(
+        &self
+    ) -> Vec<usize> {
+        self.cells.iter()
+            .enumerate()
+            .filter(|(_, &c)| c == b'O')
+            .map(|(i, _)| i)
+            .collect()
+    }
 }
 
-fn move_o_to_right(
-    leveler: &mut Array2<char>
-) {
-    for mut row in leveler.rows_mut() {
-        let mut last_hash = row.len();
-        for i in (0..row.len()).rev() {
-            if row[i] == '#' {
-                last_hash = i;
-            } else if row[i] == 'O' {
-                row.swap(i, last_hash - 1);
-                last_hash -= 1;
+/// Spin cycles run until a platform state repeats. States after `offset` cycles
+/// repeat every `period` cycles; `loads[i]` is the north load after `i` cycles.
+#[derive(Debug, PartialEq)]
+struct SpinCycle {
+    offset: usize,
+    period: usize,
+    loads: Vec<i64>,
+}
+
+impl SpinCycle {
+    fn detect(
+        mut platform: Platform
+    ) -> Self {
+        let mut history: HashMap<Vec<usize>, usize> = HashMap::new();
+        let mut loads = Vec::new();
+
+        loop {
+            let key = platform.state_key();
+            if let Some(&offset) = history.get(&key) {
+                return SpinCycle { offset, period: loads.len() - offset, loads };
             }
+
+            history.insert(key, loads.len());
+            loads.push(platform.load(Direction::North));
+            platform.spin_cycle();
         }
     }
+
+    fn load_after(
+        &self,
+        cycles: u64,
+    ) -> i64 {
+        let cycles = cycles as u128;
+        let offset = self.offset as u128;
+
+        let index = if cycles < offset {
+            cycles
+        } else {
+            offset + (cycles - offset) % self.period as u128
+        };
+
+        self.loads[index as usize]
+    }
 }
 
-fn part_1(
-    contents: &String
-) -> i64 {
-    let mut leveler = convert_input(contents);
+/// A snapshot of the platform after a tilt, `tilt` is `None` for the initial state.
+/// The last frame of a recording carries the repetition once it has been found.
+#[derive(Debug)]
+struct Frame {
+    cycle: usize,
+    tilt: Option<Direction>,
+    north_load: i64,
+    platform: Platform,
+    repeat: Option<(usize, usize)>,
+}
 
-    rotate_90_degrees_clockwise(&mut leveler);
+impl Frame {
+    fn caption(
+        &self
+    ) -> String {
+        let mut caption = match self.tilt {
+            Some(direction) => format!("Cycle {}, tilted {:?}", self.cycle, direction),
+            None => "Initial state".to_string(),
+        };
+        caption.push_str(&format!(", north load {}", self.north_load));
 
-    move_o_to_right(&mut leveler);
+        if let Some((offset, period)) = self.repeat {
+            caption.push_str(&format!(
+                ", same state as after cycle {}: period {}",
+                offset, period
+            ));
+        }
+
+        caption
+    }
+}
 
-    rotate_90_degrees_opposite(&mut leveler);
+/// Records every tilt of every spin cycle until a state repeats or `max_cycles` have run.
+fn record_frames(
+    mut platform: Platform,
+    max_cycles: Option<usize>,
+) -> Vec<Frame> {
+    let spin_cycle = SpinCycle::detect(platform.clone());
+    let repeat_at = spin_cycle.offset + spin_cycle.period;
+    let cycles = max_cycles.map_or(repeat_at, |max_cycles| max_cycles.min(repeat_at));
+
+    let mut frames = vec![Frame {
+        cycle: 0,
+        tilt: None,
+        north_load: platform.load(Direction::North),
+        platform: platform.clone(),
+        repeat: None,
+    }];
+
+    for cycle in 0..cycles {
+        for direction in [Direction::North, Direction::West, Direction::South, Direction::East] {
+            platform.tilt(direction);
+            frames.push(Frame {
+                cycle: cycle + 1,
+                tilt: Some(direction),
+                north_load: platform.load(Direction::North),
+                platform: platform.clone(),
+                repeat: None,
+            });
+        }
+    }
 
-    leveler.axis_iter(Axis(0)).rev().enumerate()
-        .map(|(i, row)| {
-            row.iter().filter(|&&c| c == 'O')
-            .count() as i64 * (i as i64 + 1)
-        })
-        .sum()
+    if cycles == repeat_at {
+        frames.last_mut().unwrap().repeat = Some((spin_cycle.offset, spin_cycle.period));
+    }
+
+    frames
 }
 
-fn part_2(
-    contents: &String
-) -> i64 {
-    let mut leveler = convert_input(contents);
-    let mut sum: i64 = 0;
-
-    // my cycle is 408 and ive calculated the the first correct answer index
-    // 4b - (((4b / 408) - 1) * 408)
-    for i in 0..640 {
-        rotate_90_degrees_clockwise(&mut leveler);
-        move_o_to_right(&mut leveler);
-        let mut copy = leveler.clone();
-        for _ in 0..(i % 4)+1 {
-            rotate_90_degrees_opposite(&mut copy);
+fn animate(
+    frames: &[Frame],
+    delay: Duration,
+) {
+    for frame in frames {
+        // Clear the screen and move the cursor home before every frame
+        print!("\x1b[2J\x1b[H");
+        println!("{}\n{}", frame.caption(), frame.platform.render());
+        thread::sleep(delay);
+    }
+}
+
+fn export_text_frames(
+    frames: &[Frame],
+    dir: &Path,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    for (i, frame) in frames.iter().enumerate() {
+        fs::write(
+            dir.join(format!("frame_{:05}.txt", i)),
+            format!("{}\n{}\n", frame.caption(), frame.platform.render()),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// 3x5 pixel glyphs for PNG captions, one row per byte with the leftmost pixel in bit 2.
+fn glyph(
+    c: char,
+) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'C' => [0b111, 0b100, 0b100, 0b100, 0b111],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        _ => [0; 5],
+    }
+}
+
+/// RGB pixels of a frame: the platform, then a banner reading "C<cycle> L<north load>"
+/// above a bar showing the north load against `max_load`. Returns (width, height, pixels).
+fn frame_image(
+    frame: &Frame,
+    max_load: i64,
+) -> (usize, usize, Vec<u8>) {
+    let platform = &frame.platform;
+    let width = platform.width * PNG_CELL_SIZE;
+    let grid_height = platform.height * PNG_CELL_SIZE;
+    let scale = (width / 80).max(1);
+    let height = grid_height + 10 * scale;
+
+    let mut pixels = vec![0; width * height * 3];
+    let mut set = |x: usize, y: usize, colour: [u8; 3]| {
+        if x < width && y < height {
+            pixels[(y * width + x) * 3..][..3].copy_from_slice(&colour);
         }
+    };
 
-        sum = leveler.axis_iter(Axis(0)).rev().enumerate()
-            .map(|(i, row)| {
-                row.iter().filter(|&&c| c == 'O')
-                .count() as i64 * (i as i64 + 1)
-            })
-            .sum();
-        dbg!(sum);
+    for y in 0..grid_height {
+        for x in 0..width {
+            set(x, y, match platform.cells[y / PNG_CELL_SIZE * platform.width + x / PNG_CELL_SIZE] {
+                b'O' => [230, 160, 40],
+                b'#' => [120, 120, 120],
+                _ => [20, 20, 30],
+            });
+        }
+    }
+
+    let text_top = grid_height + scale;
+    let caption = format!("C{} L{}", frame.cycle, frame.north_load);
+    for (i, c) in caption.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in (0..3).filter(|column| bits & (0b100 >> column) != 0) {
+                for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                    set(scale * (1 + 4 * i + column) + dx, text_top + scale * row + dy, [255, 255, 255]);
+                }
+            }
+        }
     }
 
-    sum
+    let bar_top = text_top + 6 * scale;
+    let bar_width = width * frame.north_load.max(0) as usize / max_load.max(1) as usize;
+    for y in bar_top..bar_top + 2 * scale {
+        for x in 0..bar_width {
+            set(x, y, [230, 160, 40]);
+        }
+    }
+
+    (width, height, pixels)
+}
+
+/// Writes frames as PNG images with the caption drawn in a banner under the platform
+/// and also stored in a `Comment` text chunk.
+fn export_png_frames(
+    frames: &[Frame],
+    dir: &Path,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let max_load = frames.iter().map(|frame| frame.north_load).max().unwrap_or(0);
+
+    for (i, frame) in frames.iter().enumerate() {
+        let (width, height, pixels) = frame_image(frame, max_load);
+
+        let file = File::create(dir.join(format!("frame_{:05}.png", i)))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_text_chunk("Comment".to_string(), frame.caption())?;
+        encoder.write_header()?.write_image_data(&pixels)?;
+    }
+
+    Ok(())
+}
+
+fn part_1(
+    contents: &str
+) -> i64 {
+    let mut platform = Platform::parse(contents);
+
+    platform.tilt(Direction::North);
+
+    platform.load(Direction::North)
+}
+
+fn load_after_cycles(
+    contents: &str,
+    cycles: u64,
+) -> i64 {
+    SpinCycle::detect(Platform::parse(contents)).load_after(cycles)
+}
+
+fn part_2(
+    contents: &str
+) -> i64 {
+    load_after_cycles(contents, 1_000_000_000)
 }
 
 #[cfg(test)]
@@ -133,4 +440,68 @@ mod test {
 
         assert_eq!(part_2(&contents), 64);
     }
+
+    #[test]
+    fn test_spin_cycle_detection() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let spin_cycle = SpinCycle::detect(Platform::parse(&contents));
+        assert_eq!((spin_cycle.offset, spin_cycle.period), (3, 7));
+        assert_eq!(spin_cycle.load_after(1), 87);
+        assert_eq!(load_after_cycles(&contents, u64::MAX), spin_cycle.load_after(3 + (u64::MAX - 3) % 7));
+    }
+
+    #[test]
+    fn test_tilt() {
+        let mut platform = Platform::parse("O.#.\n.O.O\n#..O");
+
+        platform.tilt(Direction::East);
+        assert_eq!(platform, Platform::parse(".O#.\n..OO\n#..O"));
+        assert_eq!(platform.load(Direction::East), 2 + 3 + 4 + 4);
+
+        platform.tilt(Direction::South);
+        assert_eq!(platform, Platform::parse("..#.\n...O\n#OOO"));
+        assert_eq!(platform.load(Direction::South), 3 + 3 + 3 + 2);
+        assert_eq!(platform.load(Direction::North), 1 + 1 + 1 + 2);
+        assert_eq!(platform.load(Direction::West), 3 + 2 + 1 + 1);
+    }
+
+    #[test]
+    fn test_record_frames() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let frames = record_frames(Platform::parse(&contents), None);
+        assert_eq!(frames.len(), 1 + 4 * 10);
+        assert_eq!(frames[1].caption(), "Cycle 1, tilted North, north load 136");
+        assert_eq!(
+            frames.last().unwrap().caption(),
+            "Cycle 10, tilted East, north load 69, same state as after cycle 3: period 7"
+        );
+
+        assert_eq!(record_frames(Platform::parse(&contents), Some(2)).len(), 9);
+        assert_eq!(record_frames(Platform::parse(&contents), Some(2))[8].repeat, None);
+    }
+
+    #[test]
+    fn test_frame_image() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let frames = record_frames(Platform::parse(&contents), Some(1));
+        let (width, height, pixels) = frame_image(&frames[1], 136);
+        assert_eq!((width, height), (40, 50));
+
+        let pixel = |x: usize, y: usize| &pixels[(y * width + x) * 3..][..3];
+        // Top left pixel of the "C", then a full load bar along the bottom
+        assert_eq!(pixel(1, 41), [255, 255, 255]);
+        assert_eq!(pixel(39, 48), [230, 160, 40]);
+
+        let (_, _, pixels) = frame_image(&frames[4], 136);
+        assert_eq!(&pixels[(48 * width + 39) * 3..][..3], [0, 0, 0]);
+    }
 }
@@ -1,41 +1,145 @@
-use std::{fs, collections::BTreeMap};
+use std::{env, fs};
 use std::convert::TryFrom;
-use anyhow;
+use anyhow::{anyhow, bail, Context};
+
+/// Number of boxes in the Lens Library, one per possible `hash` value.
+const NUM_BOXES: usize = 256;
 
 #[derive(Debug)]
 struct Lens {
     label: String,
-    hashed_label: i64,
     focal_length: Option<i64>,
 }
 
 impl TryFrom<&str> for Lens {
     type Error = anyhow::Error;
 
+    /// Parses `label=N` with a focal length from 1 to 9, or `label-`.
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let mut splited_lens = value.split(|c| c == '=' || c == '-');
-        let label = splited_lens.next().unwrap();
-        let focal_length = splited_lens
-            .next()
-            .unwrap()
-            .parse::<i64>()
-            .ok();
+        let (label, focal_length) = if let Some(label) = value.strip_suffix('-') {
+            (label, None)
+        } else if let Some((label, focal_length)) = value.split_once('=') {
+            let focal_length = focal_length
+                .parse::<i64>()
+                .ok()
+                .filter(|focal_length| (1..=9).contains(focal_length))
+                .ok_or_else(|| anyhow!("invalid focal length {:?}, expected 1 to 9", focal_length))?;
+            (label, Some(focal_length))
+        } else {
+            bail!("expected '=' and a focal length, or a trailing '-'");
+        };
+
+        if label.is_empty() || !label.chars().all(|c| c.is_ascii_lowercase()) {
+            bail!("invalid label {:?}, expected lowercase letters", label);
+        }
 
         Ok(Lens { 
             label: label.to_string(), 
-            hashed_label: hash(label), 
-            focal_length: focal_length, 
+            focal_length, 
         })
     }
 }
 
+/// The puzzle's HASHMAP: 256 boxes picked by `hash` of the key, each keeping its
+/// entries in insertion order. Replacing a value keeps its slot, removing one shifts
+/// the entries behind it forward.
+#[derive(Debug)]
+struct HashMapLib<K, V> {
+    boxes: Vec<Vec<(K, V)>>,
+}
+
+impl<K: AsRef<str>, V> HashMapLib<K, V> {
+    fn new() -> Self {
+        Self { boxes: (0..NUM_BOXES).map(|_| Vec::new()).collect() }
+    }
+
+    fn box_of(key: &str) -> usize {
+        hash(key) as usize
+    }
+
+    /// Inserts `value` at the end of the key's box, or replaces the value in place if
+    /// the key is already there, returning the old value.
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let entries = &mut self.boxes[Self::box_of(key.as_ref())];
+
+        match entries.iter_mut().find(|(k, _)| k.as_ref() == key.as_ref()) {
+            Some((_, v)) => Some(std::mem::replace(v, value)),
+            None => {
+                entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<V> {
+        let entries = &mut self.boxes[Self::box_of(key)];
+        let position = entries.iter().position(|(k, _)| k.as_ref() == key)?;
+
+        Some(entries.remove(position).1)
+    }
+
+    fn get(&self, key: &str) -> Option<&V> {
+        self.boxes[Self::box_of(key)].iter()
+            .find(|(k, _)| k.as_ref() == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Boxes holding at least one entry, with their entries in slot order.
+    fn non_empty_boxes(&self) -> impl Iterator<Item = (usize, &[(K, V)])> {
+        self.boxes.iter().enumerate()
+            .filter(|(_, entries)| !entries.is_empty())
+            .map(|(box_number, entries)| (box_number, entries.as_slice()))
+    }
+
+    /// Entries as `(box, slot, key, value)`, in box order and then slot order.
+    fn iter(&self) -> impl Iterator<Item = (usize, usize, &K, &V)> {
+        self.boxes.iter().enumerate()
+            .flat_map(|(box_number, entries)| {
+                entries.iter().enumerate()
+                    .map(move |(slot, (k, v))| (box_number, slot, k, v))
+            })
+    }
+}
+
+impl<K: AsRef<str>, V: Copy + Into<i64>> HashMapLib<K, V> {
+    /// Sum over all lenses of (box + 1) * (slot + 1) * focal length.
+    fn focusing_power(&self) -> i64 {
+        self.iter()
+            .map(|(box_number, slot, _, &focal_length)| {
+                (box_number as i64 + 1) * (slot as i64 + 1) * focal_length.into()
+            })
+            .sum()
+    }
+}
+
 fn main() {
     let file_path = "input.txt";
 
     let contents = fs::read_to_string(file_path).unwrap();
 
     println!("Sum part 1: {}", part_1(&contents));
-    println!("Sum part 2: {}", part_2(&contents));
+    match part_2(&contents) {
+        Ok(sum) => println!("Sum part 2: {}", sum),
+        Err(err) => println!("Part 2 failed: {:#}", err),
+    }
+
+    let args: Vec<String> = env::args().collect();
+    if let Some(label) = args.iter().position(|arg| arg == "--lens").and_then(|i| args.get(i + 1)) {
+        match build_book(&contents) {
+            Ok(book) => match book.get(label) {
+                Some(focal_length) => println!("Lens {} has focal length {}", label, focal_length),
+                None => println!("Lens {} is not in any box", label),
+            },
+            Err(err) => println!("Invalid initialization sequence: {:#}", err),
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--trace") {
+        match trace(&contents) {
+            Ok(trace) => print!("{}", trace),
+            Err(err) => println!("Invalid initialization sequence: {:#}", err),
+        }
+    }
 }
 
 fn hash(
@@ -45,55 +149,89 @@ fn hash(
         .fold(0, |num, c| (num + c as i64) * 17 % 256)
 }
 
+/// Splits the initialization sequence on commas, ignoring newlines anywhere in it and
+/// whitespace around each step.
+fn split_steps(
+    contents: &str,
+) -> Vec<String> {
+    let sequence: String = contents.chars().filter(|&c| c != '\n' && c != '\r').collect();
+
+    sequence.split(',')
+        .map(|step| step.trim().to_string())
+        .collect()
+}
+
+fn parse_steps(
+    contents: &str,
+) -> anyhow::Result<Vec<Lens>> {
+    split_steps(contents).iter()
+        .enumerate()
+        .map(|(i, step)| {
+            Lens::try_from(step.as_str()).with_context(|| format!("step {} ({:?})", i + 1, step))
+        })
+        .collect()
+}
+
 fn part_1(
-    contents: &String,
+    contents: &str,
 ) -> i64 {
-    contents.split(",")
-        .map(|s| hash(s))
+    split_steps(contents).iter()
+        .map(|step| hash(step))
         .sum()
 }
 
 fn proceed_lens(
     lens: Lens,
-    book: &mut BTreeMap<i64, Vec<Lens>>,
+    book: &mut HashMapLib<String, i64>,
 ) {
-    let entry = book.entry(lens.hashed_label).or_insert(Vec::new());
-    let position = entry.iter().position(|l| l.label == lens.label);
-
     match lens.focal_length {
-        Some(_) => {
-            if let Some(index) = position {
-                entry[index] = lens;
-            } else {
-                entry.push(lens);
-            }
+        Some(focal_length) => {
+            book.insert(lens.label, focal_length);
         },
         None => {
-            if position.is_some() {
-                entry.retain(|l| l.label != lens.label);
-            }
+            book.remove(&lens.label);
         },
     }
 }
 
-fn part_2(
-    contents: &String,
-) -> i64 {
-    let book = contents.split(",")
-        .map(|s| Lens::try_from(s).unwrap())
-        .fold(BTreeMap::new(), |mut book, lens| {
+fn build_book(
+    contents: &str,
+) -> anyhow::Result<HashMapLib<String, i64>> {
+    Ok(parse_steps(contents)?.into_iter()
+        .fold(HashMapLib::new(), |mut book, lens| {
             proceed_lens(lens, &mut book);
             book
-        });
-
-    book.iter()
-        .filter(|(_, lenses)| lenses.len() > 0)
-        .map(|(&num, lenses)| {
-            lenses.iter().enumerate()
-                .map(|(pos, lens)| (num + 1) * (pos as i64 + 1) * lens.focal_length.unwrap())
-                .sum::<i64>()
-        })
-        .sum()
+        }))
+}
+
+/// The box contents after every step, formatted as in the puzzle description.
+fn trace(
+    contents: &str,
+) -> anyhow::Result<String> {
+    let steps = split_steps(contents);
+    let mut book = HashMapLib::new();
+    let mut trace = String::new();
+
+    for (step, lens) in steps.iter().zip(parse_steps(contents)?) {
+        proceed_lens(lens, &mut book);
+
+        trace.push_str(&format!("After {:?}:\n", step));
+        for (box_number, entries) in book.non_empty_boxes() {
+            let lenses: Vec<String> = entries.iter()
+                .map(|(label, focal_length)| format!("[{} {}]", label, focal_length))
+                .collect();
+            trace.push_str(&format!("Box {}: {}\n", box_number, lenses.join(" ")));
+        }
+        trace.push('\n');
+    }
+
+    Ok(trace)
+}
+
+fn part_2(
+    contents: &str,
+) -> anyhow::Result<i64> {
+    Ok(build_book(contents)?.focusing_power())
 }
 
 #[cfg(test)]
@@ -115,6 +253,53 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 145);
+        assert_eq!(part_2(&contents).unwrap(), 145);
+    }
+
+    #[test]
+    fn test_hash_map_lib() {
+        let mut book: HashMapLib<&str, i64> = HashMapLib::new();
+
+        assert_eq!(book.insert("rn", 1), None);
+        assert_eq!(book.insert("cm", 2), None);
+        assert_eq!(book.insert("ot", 9), None);
+        assert_eq!(book.insert("rn", 5), Some(1));
+        assert_eq!(book.get("rn"), Some(&5));
+
+        assert_eq!(book.iter().map(|(b, slot, &k, _)| (b, slot, k)).collect::<Vec<_>>(), vec![
+            (0, 0, "rn"),
+            (0, 1, "cm"),
+            (3, 0, "ot"),
+        ]);
+
+        assert_eq!(book.remove("rn"), Some(5));
+        assert_eq!(book.remove("rn"), None);
+        assert_eq!(book.get("cm"), Some(&2));
+        assert_eq!(book.focusing_power(), 2 + 4 * 9);
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        assert_eq!(part_1("rn=1,cm-\n"), part_1("rn=1,cm-"));
+        assert_eq!(part_2("rn=1,\ncm-,qp=3\n").unwrap(), 1 + 2 * 3);
+
+        let err = part_2("rn=1,cm-,qp=x").unwrap_err();
+        assert_eq!(format!("{:#}", err), "step 3 (\"qp=x\"): invalid focal length \"x\", expected 1 to 9");
+
+        let err = part_2("rn=1,,cm-").unwrap_err();
+        assert_eq!(format!("{:#}", err), "step 2 (\"\"): expected '=' and a focal length, or a trailing '-'");
+
+        let err = part_2("Rn=1").unwrap_err();
+        assert_eq!(format!("{:#}", err), "step 1 (\"Rn=1\"): invalid label \"Rn\", expected lowercase letters");
+    }
+
+    #[test]
+    fn test_trace() {
+        let trace = trace("rn=1,cm-,qp=3,cm=2").unwrap();
+
+        assert_eq!(trace, "After \"rn=1\":\nBox 0: [rn 1]\n\n\
+            After \"cm-\":\nBox 0: [rn 1]\n\n\
+            After \"qp=3\":\nBox 0: [rn 1]\nBox 1: [qp 3]\n\n\
+            After \"cm=2\":\nBox 0: [rn 1] [cm 2]\nBox 1: [qp 3]\n\n");
     }
 }
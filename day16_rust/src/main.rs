@@ -1,4 +1,5 @@
-use std::{fs, collections::{HashSet, HashMap}};
+use std::{env, fs, collections::{HashMap, HashSet}};
+use rayon::prelude::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Point {
@@ -19,66 +20,455 @@ impl Point {
     }
 }
 
-fn main() {
-    let file_path = "input.txt";
+const RIGHT: Point = Point { x: 1, y: 0 };
+const DOWN: Point = Point { x: 0, y: 1 };
+const LEFT: Point = Point { x: -1, y: 0 };
+const UP: Point = Point { x: 0, y: -1 };
+
+/// A straight run of the beam from `start` to `end`, both inclusive, travelling `dir`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BeamSegment {
+    start: Point,
+    end: Point,
+    dir: Point,
+}
 
-    let contents = fs::read_to_string(file_path).unwrap();
+/// Shades used to draw edge heat strips, from coolest to hottest.
+const HEAT_SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
 
-    println!("Max part 1: {}", part_1(&contents));
-    println!("Max part 2: {}", part_2(&contents));
+/// Energized tile counts for every entry point, per edge in reading order.
+#[derive(Debug, PartialEq)]
+struct EdgeHeat {
+    top: Vec<usize>,
+    bottom: Vec<usize>,
+    left: Vec<usize>,
+    right: Vec<usize>,
 }
 
-fn get_grid(
-    contents: &String,
-) -> HashMap<Point, char> {
-    let mut grid = HashMap::new();
+/// Fixed-size bitset over the tiles of a contraption.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TileSet {
+    words: Vec<u64>,
+}
 
-    contents.lines().enumerate().for_each(|(i, line)| {
-        line.chars().enumerate().for_each(|(j, c)| {
-            grid.insert(Point { x: j as i32, y: i as i32 }, c);
-        });
-    });
+impl TileSet {
+    fn new(num_tiles: usize) -> Self {
+        Self { words: vec![0; num_tiles.div_ceil(64)] }
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn union_with(&mut self, other: &Self) {
+        self.words.iter_mut().zip(&other.words).for_each(|(word, other)| *word |= other);
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+/// Splitters hit on their flat side are the nodes of a graph: each one sends beams
+/// that either leave the grid or activate further splitters. Splitters in one strongly
+/// connected component light the same tiles, so `component_tiles[component_of[n]]`
+/// holds every tile energized once splitter `n` is activated.
+struct SplitterGraph {
+    nodes: HashMap<usize, usize>,
+    component_of: Vec<usize>,
+    component_tiles: Vec<TileSet>,
+}
+
+impl SplitterGraph {
+    fn reachable(&self, splitter: usize) -> &TileSet {
+        &self.component_tiles[self.component_of[self.nodes[&splitter]]]
+    }
+}
+
+/// The mirror layout as a dense row-major grid, it does not need to be square.
+struct Contraption {
+    width: usize,
+    height: usize,
+    tiles: Vec<char>,
+}
+
+/// Result of following one beam. `visited` holds a bit per direction for every tile,
+/// so a tile is energized when any of its bits is set.
+struct BeamPath {
+    width: usize,
+    visited: Vec<u8>,
+    segments: Vec<BeamSegment>,
+}
+
+impl BeamPath {
+    fn energized_count(&self) -> usize {
+        self.visited.iter().filter(|&&dirs| dirs != 0).count()
+    }
+
+    /// How many distinct beam directions crossed each tile, from 0 to 4.
+    fn crossings(&self) -> Vec<u32> {
+        self.visited.iter().map(|dirs| dirs.count_ones()).collect()
+    }
 
-    grid
+    fn energized_tiles(&self) -> HashSet<Point> {
+        self.visited.iter()
+            .enumerate()
+            .filter(|(_, &dirs)| dirs != 0)
+            .map(|(i, _)| Point::new((i % self.width) as i32, (i / self.width) as i32))
+            .collect()
+    }
 }
 
-fn move_beam(
-    grid: &HashMap<Point, char>,
-    pos: &Point,
-    dir: &Point,
-    energized: &mut HashSet<Point>,
-    map_point_to_dir: &mut HashMap<Point, Point>,
-) {
-    if let Some(old_dir) = map_point_to_dir.get(pos) {
-        if *old_dir == *dir {
-            return;
+fn direction_bit(
+    dir: Point,
+) -> u8 {
+    match dir {
+        RIGHT => 1,
+        DOWN => 2,
+        LEFT => 4,
+        UP => 8,
+        _ => panic!("Invalid direction"),
+    }
+}
+
+impl Contraption {
+    fn index(&self, pos: Point) -> Option<usize> {
+        let in_bounds = pos.x >= 0
+            && pos.y >= 0
+            && (pos.x as usize) < self.width
+            && (pos.y as usize) < self.height;
+
+        in_bounds.then(|| pos.y as usize * self.width + pos.x as usize)
+    }
+
+    /// Follows the beam entering `start` travelling `dir` with a work list instead of
+    /// recursion. Each (tile, direction) pair is processed at most once, so loops end.
+    fn propagate(&self, start: Point, dir: Point) -> BeamPath {
+        let mut visited = vec![0u8; self.tiles.len()];
+        let mut segments = Vec::new();
+        let mut work = vec![(start, dir)];
+
+        while let Some((segment_start, dir)) = work.pop() {
+            let mut pos = segment_start;
+            let Some(mut i) = self.index(pos) else {
+                continue;
+            };
+            if visited[i] & direction_bit(dir) != 0 {
+                continue;
+            }
+
+            loop {
+                visited[i] |= direction_bit(dir);
+                let new_dirs = get_dir(self.tiles[i], dir);
+
+                // Keep walking while the beam goes straight on through fresh tiles
+                let next = pos.add(&dir);
+                if new_dirs == [dir] {
+                    if let Some(next_i) = self.index(next) {
+                        if visited[next_i] & direction_bit(dir) == 0 {
+                            pos = next;
+                            i = next_i;
+                            continue;
+                        }
+                    }
+                }
+
+                segments.push(BeamSegment { start: segment_start, end: pos, dir });
+                work.extend(new_dirs.into_iter().map(|new_dir| (pos.add(&new_dir), new_dir)));
+                break;
+            }
+        }
+
+        BeamPath { width: self.width, visited, segments }
+    }
+
+    /// Walks one beam through mirrors and splitters passed end-on, marking tiles in
+    /// `tiles`, until it leaves the grid or hits a splitter on its flat side, whose
+    /// index is returned. A beam can only cycle back to where it started, so the walk
+    /// is cut off after every (tile, direction) pair could have been seen once.
+    fn trace_until_split(&self, mut pos: Point, mut dir: Point, tiles: &mut TileSet) -> Option<usize> {
+        for _ in 0..4 * self.tiles.len() {
+            let i = self.index(pos)?;
+            tiles.insert(i);
+
+            let new_dirs = get_dir(self.tiles[i], dir);
+            if new_dirs.len() > 1 {
+                return Some(i);
+            }
+
+            dir = new_dirs[0];
+            pos = pos.add(&dir);
         }
+
+        None
     }
 
-    let grid_size = (grid.len() as f64).sqrt() as i32;
+    /// Builds the splitter graph and memoises the tiles reachable from every splitter
+    /// by condensing strongly connected components and taking bitset unions in
+    /// reverse topological order.
+    fn splitter_graph(&self) -> SplitterGraph {
+        let splitters: Vec<usize> = (0..self.tiles.len())
+            .filter(|&i| matches!(self.tiles[i], '|' | '-'))
+            .collect();
+        let nodes: HashMap<usize, usize> = splitters.iter()
+            .enumerate()
+            .map(|(node, &i)| (i, node))
+            .collect();
+
+        // Tiles lit by each splitter's own outgoing beams and the splitters they activate
+        let (mut local, edges): (Vec<TileSet>, Vec<Vec<usize>>) = splitters.par_iter()
+            .map(|&i| {
+                let pos = Point::new((i % self.width) as i32, (i / self.width) as i32);
+                let mut tiles = TileSet::new(self.tiles.len());
+                tiles.insert(i);
+
+                let out_dirs = if self.tiles[i] == '|' { [UP, DOWN] } else { [LEFT, RIGHT] };
+                let targets = out_dirs.iter()
+                    .filter_map(|dir| self.trace_until_split(pos.add(dir), *dir, &mut tiles))
+                    .map(|target| nodes[&target])
+                    .collect();
+
+                (tiles, targets)
+            })
+            .unzip();
+
+        let components = strongly_connected_components(&edges);
+
+        // Components come out in topological order, so walk them backwards
+        let mut component_of = vec![0; splitters.len()];
+        components.iter().enumerate().for_each(|(c, members)| {
+            members.iter().for_each(|&node| component_of[node] = c);
+        });
 
-    if pos.x >= 0 
-        && pos.y >= 0 
-        && pos.x < grid_size 
-        && pos.y < grid_size {
-        energized.insert(*pos);
-        map_point_to_dir.insert(*pos, *dir);
+        // Each splitter's own tiles are freed as soon as they are merged into its component
+        let mut component_tiles: Vec<TileSet> = vec![TileSet::default(); components.len()];
+        for (c, members) in components.iter().enumerate().rev() {
+            let mut tiles = TileSet::new(self.tiles.len());
+            for &node in members {
+                tiles.union_with(&std::mem::take(&mut local[node]));
+                for &target in &edges[node] {
+                    if component_of[target] != c {
+                        tiles.union_with(&component_tiles[component_of[target]]);
+                    }
+                }
+            }
+            component_tiles[c] = tiles;
+        }
+
+        SplitterGraph { nodes, component_of, component_tiles }
     }
 
-    let next_pos = pos.add(&dir);
+    fn energized_from(&self, graph: &SplitterGraph, start: Point, dir: Point) -> usize {
+        let mut tiles = TileSet::new(self.tiles.len());
+
+        if let Some(splitter) = self.trace_until_split(start, dir, &mut tiles) {
+            tiles.union_with(graph.reachable(splitter));
+        }
 
-    match grid.get(&next_pos) {
-        Some('.') => move_beam(grid, &next_pos, dir, energized, map_point_to_dir),
-        Some(c) => {
-            let new_dirs = get_dir(*c, *dir);
-            new_dirs.iter().for_each(|new_dir| {
-                move_beam(grid, &next_pos, new_dir, energized, map_point_to_dir);
+        tiles.len()
+    }
+
+    /// The edge entry energizing the most tiles, as (entry tile, direction, count),
+    /// checking all entries in parallel against the memoised splitter graph.
+    fn best_entry(&self, graph: &SplitterGraph) -> Option<(Point, Point, usize)> {
+        self.edge_entries().into_par_iter()
+            .map(|(start, dir)| (start, dir, self.energized_from(graph, start, dir)))
+            .max_by_key(|&(_, _, count)| count)
+    }
+
+    fn edge_heat(&self, graph: &SplitterGraph) -> EdgeHeat {
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        let heat = |entries: Vec<(Point, Point)>| -> Vec<usize> {
+            entries.into_par_iter()
+                .map(|(start, dir)| self.energized_from(graph, start, dir))
+                .collect()
+        };
+
+        EdgeHeat {
+            top: heat((0..width).map(|x| (Point::new(x, 0), DOWN)).collect()),
+            bottom: heat((0..width).map(|x| (Point::new(x, height - 1), UP)).collect()),
+            left: heat((0..height).map(|y| (Point::new(0, y), RIGHT)).collect()),
+            right: heat((0..height).map(|y| (Point::new(width - 1, y), LEFT)).collect()),
+        }
+    }
+
+    /// Draws the contraption with energized tiles highlighted. Without colour,
+    /// energized tiles are drawn as `#` and the rest as `.`, as in the puzzle.
+    fn render(&self, path: &BeamPath, colour: bool) -> String {
+        self.tiles.chunks(self.width)
+            .zip(path.visited.chunks(self.width))
+            .map(|(row, visited)| {
+                row.iter()
+                    .zip(visited)
+                    .map(|(&tile, &dirs)| match (colour, dirs != 0) {
+                        (true, true) => format!("\x1b[30;43m{}\x1b[0m", tile),
+                        (true, false) => tile.to_string(),
+                        (false, true) => "#".to_string(),
+                        (false, false) => ".".to_string(),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Binary PPM image with one pixel per tile, brighter the more beam directions
+    /// crossed it. Mirrors and splitters that stayed dark are drawn in grey.
+    fn heatmap_ppm(&self, path: &BeamPath) -> Vec<u8> {
+        let mut image = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+
+        for (&tile, crossings) in self.tiles.iter().zip(path.crossings()) {
+            image.extend_from_slice(&match (crossings, tile) {
+                (0, '.') => [0, 0, 0],
+                (0, _) => [70, 70, 70],
+                (1, _) => [120, 30, 0],
+                (2, _) => [200, 80, 0],
+                (3, _) => [255, 170, 0],
+                _ => [255, 255, 160],
             });
         }
-        None => return,
+
+        image
+    }
+
+    /// Every way a beam can enter from the edge, as (entry tile, direction).
+    fn edge_entries(&self) -> Vec<(Point, Point)> {
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        (0..height)
+            .flat_map(|y| [(Point::new(0, y), RIGHT), (Point::new(width - 1, y), LEFT)])
+            .chain((0..width).flat_map(|x| [(Point::new(x, 0), DOWN), (Point::new(x, height - 1), UP)]))
+            .collect()
+    }
+}
+
+/// Kosaraju's algorithm with explicit stacks. Components are returned in topological
+/// order of the condensed graph.
+fn strongly_connected_components(
+    edges: &[Vec<usize>],
+) -> Vec<Vec<usize>> {
+    let mut reverse_edges = vec![Vec::new(); edges.len()];
+    edges.iter().enumerate().for_each(|(node, targets)| {
+        targets.iter().for_each(|&target| reverse_edges[target].push(node));
+    });
+
+    let mut visited = vec![false; edges.len()];
+    let mut finish_order = Vec::new();
+    for root in 0..edges.len() {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            if let Some(&target) = edges[*node].get(*next) {
+                *next += 1;
+                if !visited[target] {
+                    visited[target] = true;
+                    stack.push((target, 0));
+                }
+            } else {
+                finish_order.push(*node);
+                stack.pop();
+            }
+        }
+    }
+
+    let mut assigned = vec![false; edges.len()];
+    let mut components = Vec::new();
+    for &root in finish_order.iter().rev() {
+        if assigned[root] {
+            continue;
+        }
+        assigned[root] = true;
+        let mut component = vec![root];
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            for &source in &reverse_edges[node] {
+                if !assigned[source] {
+                    assigned[source] = true;
+                    component.push(source);
+                    stack.push(source);
+                }
+            }
+        }
+        components.push(component);
+    }
+
+    components
+}
+
+fn main() {
+    let file_path = "input.txt";
+
+    let contents = fs::read_to_string(file_path).unwrap();
+
+    let grid = get_grid(&contents);
+    let graph = grid.splitter_graph();
+
+    println!("Max part 1: {}", part_1(&contents));
+    println!("Max part 2: {}", part_2(&grid, &graph));
+
+    if let Some((start, dir, count)) = grid.best_entry(&graph) {
+        println!("Best entry: {:?} going {:?} energizes {} tiles", start, dir, count);
+    }
+
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().any(|arg| arg == "--render") {
+        println!("{}", grid.render(&grid.propagate(Point::new(0, 0), RIGHT), true));
+    }
+
+    if let Some(heatmap_path) = args.iter().position(|arg| arg == "--heatmap").and_then(|i| args.get(i + 1)) {
+        fs::write(heatmap_path, grid.heatmap_ppm(&grid.propagate(Point::new(0, 0), RIGHT))).unwrap();
+    }
+
+    if args.iter().any(|arg| arg == "--edge-heat") {
+        println!("{}", render_edge_heat(&grid.edge_heat(&graph)));
+    }
+
+    if args.iter().any(|arg| arg == "--segments") {
+        let path = grid.propagate(Point::new(0, 0), RIGHT);
+        for segment in &path.segments {
+            println!("{:?} -> {:?} going {:?}", segment.start, segment.end, segment.dir);
+        }
+        println!("{} energized tiles", path.energized_tiles().len());
     }
 }
 
+fn render_edge_heat(
+    heat: &EdgeHeat,
+) -> String {
+    let best = [&heat.top, &heat.bottom, &heat.left, &heat.right].iter()
+        .flat_map(|counts| counts.iter())
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    [("top", &heat.top), ("bottom", &heat.bottom), ("left", &heat.left), ("right", &heat.right)]
+        .iter()
+        .map(|(side, counts)| {
+            let strip: String = counts.iter()
+                .map(|&count| HEAT_SHADES[count * (HEAT_SHADES.len() - 1) / best])
+                .collect();
+            let side_best = counts.iter().max().copied().unwrap_or(0);
+            format!("{:>6} |{}| max {}", side, strip, side_best)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn get_grid(
+    contents: &str,
+) -> Contraption {
+    let width = contents.lines().next().map_or(0, |line| line.chars().count());
+    let tiles: Vec<char> = contents.lines().flat_map(|line| line.chars()).collect();
+
+    Contraption { width, height: tiles.len() / width.max(1), tiles }
+}
+
 fn get_dir(
     c: char,
     current_dir: Point,
@@ -86,6 +476,7 @@ fn get_dir(
     let mut dirs = Vec::new();
 
     match c {
+        '.' => dirs.push(current_dir),
         '/' => {
             match current_dir {
                 Point { x: 0, y: 1 } => dirs.push(Point::new(-1, 0)),
@@ -141,54 +532,20 @@ fn get_dir(
 }
 
 fn part_1(
-    contents: &String,
+    contents: &str,
 ) -> i64 {
     let grid = get_grid(contents);
 
-    let mut energized = HashSet::new();
-    let mut map_point_to_dir = HashMap::new();
-
-    move_beam(&grid, &Point::new(-1, 0), &Point::new(1, 0), &mut energized, &mut map_point_to_dir);
-
-    energized.len() as i64
+    grid.propagate(Point::new(0, 0), RIGHT).energized_count() as i64
 }
 
-fn part_2(contents: &String) -> i64 {
-    let grid = get_grid(contents);
-    let lines: Vec<&str> = contents.lines().collect();
-    let rows = lines.len();
-    let cols = lines[0].len();
-
-    let mut max = 0;
-
-    let dirs = vec![
-        Point::new(0, 1),
-        Point::new(1, 0),
-        Point::new(0, -1),
-        Point::new(-1, 0),
-    ];
-
-    let mut energized = HashSet::new();
-    let mut map_point_to_dir = HashMap::new();
-
-    let mut process_beam = |start: &Point, dir: &Point| {
-        move_beam(&grid, start, dir, &mut energized, &mut map_point_to_dir);
-        max = max.max(energized.len());
-        energized.clear();
-        map_point_to_dir.clear();
-    };
-
-    (0..rows).for_each(|i| {
-        process_beam(&Point::new(-1, i as i32), &dirs[1]);
-        process_beam(&Point::new(cols as i32, i as i32), &dirs[3]);
-    });
-
-    (0..cols).for_each(|i| {
-        process_beam(&Point::new(i as i32, -1), &dirs[0]);
-        process_beam(&Point::new(i as i32, rows as i32), &dirs[2]);
-    });
-
-    max as i64
+/// Takes the grid and its splitter graph so callers that also want the best entry
+/// itself don't build the graph twice.
+fn part_2(
+    grid: &Contraption,
+    graph: &SplitterGraph,
+) -> i64 {
+    grid.best_entry(graph).map_or(0, |(_, _, count)| count) as i64
 }
 
 #[cfg(test)]
@@ -210,6 +567,61 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 51);
+        let grid = get_grid(&contents);
+
+        assert_eq!(part_2(&grid, &grid.splitter_graph()), 51);
+    }
+
+    #[test]
+    fn test_propagate() {
+        let grid = get_grid(".|..\n....\n.-.\\");
+
+        let path = grid.propagate(Point::new(0, 0), RIGHT);
+        assert_eq!(path.energized_count(), 7);
+        assert_eq!(path.segments[0], BeamSegment { start: Point::new(0, 0), end: Point::new(1, 0), dir: RIGHT });
+        assert!(path.energized_tiles().contains(&Point::new(3, 2)));
+        assert!(!path.energized_tiles().contains(&Point::new(3, 0)));
+        assert_eq!(grid.edge_entries().len(), 2 * 3 + 2 * 4);
+    }
+
+    #[test]
+    fn test_splitter_graph_matches_propagation() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let grid = get_grid(&contents);
+        let graph = grid.splitter_graph();
+
+        for (start, dir) in grid.edge_entries() {
+            assert_eq!(
+                grid.energized_from(&graph, start, dir),
+                grid.propagate(start, dir).energized_count()
+            );
+        }
+        assert_eq!(grid.best_entry(&graph), Some((Point::new(3, 0), DOWN, 51)));
+    }
+
+    #[test]
+    fn test_render_and_heat() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let grid = get_grid(&contents);
+        let path = grid.propagate(Point::new(0, 0), RIGHT);
+
+        let rendered = grid.render(&path, false);
+        assert_eq!(rendered.lines().next(), Some("######...."));
+        assert_eq!(rendered.matches('#').count(), 46);
+
+        let ppm = grid.heatmap_ppm(&path);
+        assert!(ppm.starts_with(b"P6\n10 10\n255\n"));
+        assert_eq!(ppm.len(), "P6\n10 10\n255\n".len() + 3 * 100);
+
+        let heat = grid.edge_heat(&grid.splitter_graph());
+        assert_eq!(heat.top[3], 51);
+        assert_eq!(heat.left[0], 46);
+        assert_eq!(render_edge_heat(&heat).lines().count(), 4);
     }
 }
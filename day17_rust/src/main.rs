@@ -1,12 +1,47 @@
-use std::{fs, collections::{HashMap, BinaryHeap}, cmp::Reverse};
+use std::{env, fmt, fs, collections::BinaryHeap, cmp::Reverse};
 
 fn main() {
     let file_path = "input.txt";
 
     let contents = fs::read_to_string(file_path).unwrap();
 
-    println!("Min part 1: {}", part_1(&contents));
-    println!("Min part 2: {}", part_2(&contents));
+    match part_1(&contents) {
+        Ok(heat_loss) => println!("Min part 1: {}", heat_loss),
+        Err(err) => println!("Part 1 failed: {}", err),
+    }
+    match part_2(&contents) {
+        Ok(heat_loss) => println!("Min part 2: {}", heat_loss),
+        Err(err) => println!("Part 2 failed: {}", err),
+    }
+
+    let args: Vec<String> = env::args().collect();
+
+    let Ok(city) = City::parse(&contents) else {
+        return;
+    };
+    let configs = [
+        ("Crucible", CrucibleConfig::regular(Point::new(0, 0), city.bottom_right())),
+        ("Ultra crucible", CrucibleConfig::ultra(Point::new(0, 0), city.bottom_right())),
+    ];
+
+    if args.iter().any(|arg| arg == "--route") {
+        for (name, config) in &configs {
+            if let Some(route) = CrucibleSearch::run(&city, config).route(&city) {
+                println!("{}:", name);
+                print_route(&city, &route);
+            }
+        }
+    }
+
+    if let Some(k) = args.iter().position(|arg| arg == "--routes").and_then(|i| args.get(i + 1)) {
+        let k: usize = k.parse().unwrap();
+        for (name, config) in &configs {
+            for (i, route) in k_cheapest_routes(&city, config, k).iter().enumerate() {
+                println!("{} route {}:", name, i + 1);
+                print_route(&city, route);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -27,12 +62,12 @@ impl Point {
         }
     }
 
-    fn get_distance(&self, other: &Self) -> u16 {
-        ((self.x - other.x).abs() + (self.y - other.y).abs()) as u16
+    fn get_distance(&self, other: &Self) -> u32 {
+        ((self.x - other.x).abs() + (self.y - other.y).abs()) as u32
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Clone, Copy)]
 enum Direction {
     Up,
     Down,
@@ -40,8 +75,10 @@ enum Direction {
     Right,
 }
 
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
 impl Direction {
-    fn to_point(&self) -> Point {
+    fn to_point(self) -> Point {
         match self {
             Direction::Up => Point::new(0, -1),
             Direction::Down => Point::new(0, 1),
@@ -50,73 +87,34 @@ impl Direction {
         }
     }
 
-    fn no_return_dirs(&self) -> Vec<Direction> {
+    fn index(self) -> usize {
         match self {
-            Direction::Up => vec![Direction::Left, Direction::Right, Direction::Up],
-            Direction::Down => vec![Direction::Left, Direction::Right, Direction::Down],
-            Direction::Left => vec![Direction::Up, Direction::Down, Direction::Left],
-            Direction::Right => vec![Direction::Up, Direction::Down, Direction::Right],
+            Direction::Up => 0,
+            Direction::Down => 1,
+            Direction::Left => 2,
+            Direction::Right => 3,
         }
     }
 
-    fn turn_dirs(&self) -> Vec<Direction> {
+    fn turn_left(self) -> Direction {
         match self {
-            Direction::Up | Direction::Down => 
-                vec![Direction::Left, Direction::Right],
-            Direction::Left | Direction::Right => 
-                 vec![Direction::Down, Direction::Up],
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
         }
     }
-}
 
-#[derive(Debug, Eq, Hash, Clone)]
-struct QueueNode {
-    point: Point,
-    direction: Direction,
-    steps: u8, 
-    heat_value: u16,
-    priority: u16,
-    path: Vec<Point>,
-}
-
-impl QueueNode {
-    fn new(point: Point, end_point: &Point, direction: Direction, steps: u8,  heat_value: u16, heat_current: u16, path: Vec<Point>) -> Self {
-
-        let mut new_path = path.clone();
-        new_path.push(point);
-        Self { 
-            point, 
-            direction, 
-            steps,
-            heat_value,
-            priority: get_priority(&point, end_point, heat_current),
-            path: new_path,
+    fn turn_right(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
         }
-    } 
-}
-
-fn get_priority(start_point: &Point, end_point: &Point, heat_value: u16) -> u16 {
-    heat_value + start_point.get_distance(end_point)
-}
-
-impl PartialEq for QueueNode {
-    fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority
-    }
-}
-
-impl Ord for QueueNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.priority.cmp(&other.priority)
     }
 }
 
-impl PartialOrd for QueueNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    } 
-}
-
 #[derive(Debug)]
 struct MinHeap<T> {
     heap: BinaryHeap<Reverse<T>>,
@@ -136,222 +134,394 @@ impl<T: Ord> MinHeap<T> {
     }
 }
 
-fn get_grid_and_dp(
-    contents: &String,
-) -> HashMap<Point, u16> {
-    let mut grid = HashMap::new();
-
-    contents.lines().enumerate().for_each(|(i, line)| {
-        line.chars().enumerate().for_each(|(j, c)| {
-            grid.insert(
-                Point { x: j as i32, y: i as i32 }, 
-                c.to_string().parse::<u16>().unwrap()
-            );
-        });
-    });
-
-    grid
+#[derive(Debug, PartialEq)]
+enum CityError {
+    InvalidBlock { row: usize, column: usize, found: char },
+    RaggedRow(usize),
+    NoRoute,
 }
 
-fn min_heat_loss(
-    grid: &HashMap<Point, u16>,
-    end_point: &Point,
-    queue: &mut MinHeap<QueueNode>,
-    dp: &mut HashMap<(Point, Direction, u8), u16>,
-) -> u16 {
-    loop {
-        let node = match queue.pop() {
-            Some(node) => node,
-            None => break,
-        };
-
-        if node.point == *end_point {
-            return node.heat_value;
+impl fmt::Display for CityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CityError::InvalidBlock { row, column, found } => {
+                write!(f, "block at row {}, column {} is {:?}, expected a digit from 1 to 9", row + 1, column + 1, found)
+            }
+            CityError::RaggedRow(row) => write!(f, "row {} differs in width from the first row", row + 1),
+            CityError::NoRoute => write!(f, "the crucible cannot reach the end"),
         }
+    }
+}
 
-        let dirs = if node.steps < 2 {
-            node.direction.no_return_dirs()
-        } else {
-            node.direction.turn_dirs()
-        };
+/// Heat loss of every city block, row-major. Every block loses at least 1, which keeps
+/// the Manhattan distance an admissible A* heuristic.
+struct City {
+    width: usize,
+    height: usize,
+    heat: Vec<u8>,
+}
 
-        for dir in dirs {
-            let next_point = node.point.add(&dir.to_point());
-        
-            let next_heat = match grid.get(&next_point) {
-                Some(heat) => heat,
-                None => continue,
-            };
-        
-            let heat = dp.get(&(node.point, node.direction, node.steps)).unwrap() + next_heat;
-            let steps = if dir == node.direction {
-                node.steps + 1
-            } else {
-                0
-            };
+impl City {
+    fn parse(
+        contents: &str,
+    ) -> Result<Self, CityError> {
+        let width = contents.lines().next().map_or(0, |line| line.len());
+        let mut heat = Vec::new();
 
-            if !dp.contains_key(&(next_point, dir, steps)) || heat < *dp.get(&(next_point, dir, steps)).unwrap() {
-                dp.insert((next_point, dir, steps), heat);
-            } else {
-                continue;
+        for (row, line) in contents.lines().enumerate() {
+            if line.len() != width {
+                return Err(CityError::RaggedRow(row));
+            }
+            for (column, c) in line.bytes().enumerate() {
+                if !(b'1'..=b'9').contains(&c) {
+                    let found = line[column..].chars().next().unwrap();
+                    return Err(CityError::InvalidBlock { row, column, found });
+                }
+                heat.push(c - b'0');
             }
-        
-            let next_node = QueueNode::new(
-                next_point,
-                end_point,
-                dir,
-                steps,
-                heat,
-                heat,
-                node.path.clone(),
-            );
-        
-            queue.push(next_node);
         }
+
+        Ok(Self { width, height: heat.len() / width.max(1), heat })
+    }
+
+    fn index(&self, point: Point) -> Option<usize> {
+        let in_bounds = point.x >= 0
+            && point.y >= 0
+            && (point.x as usize) < self.width
+            && (point.y as usize) < self.height;
+
+        in_bounds.then(|| point.y as usize * self.width + point.x as usize)
+    }
+
+    fn point(&self, block: usize) -> Point {
+        Point::new((block % self.width) as i32, (block / self.width) as i32)
+    }
+
+    fn bottom_right(&self) -> Point {
+        Point::new(self.width as i32 - 1, self.height as i32 - 1)
     }
-    unreachable!()
 }
 
-fn init_queue(
-    grid: &HashMap<Point, u16>,
-    start_point: Point,
-    end_point: &Point,
-    dp: &mut HashMap<(Point, Direction, u8), u16>,
-) -> MinHeap<QueueNode> {
-    let mut queue = MinHeap::new();
+/// How a crucible may move: each straight run covers `min_straight` to `max_straight`
+/// blocks, and it may turn left and/or right between runs. It never reverses.
+#[derive(Debug, Clone, Copy)]
+struct CrucibleConfig {
+    min_straight: usize,
+    max_straight: usize,
+    start: Point,
+    end: Point,
+    turn_left: bool,
+    turn_right: bool,
+}
 
-    let heat_start = grid.get(&start_point).unwrap();
-
-    let dirs = vec![Direction::Right, Direction::Down];
-
-    let path = vec![start_point];
-
-    dp.insert((start_point, Direction::Right, 0), *heat_start);
-
-    dirs.iter().for_each(|dir| {
-        let next_point = start_point.add(&dir.to_point());
-        
-        if let Some(heat_value) = grid.get(&next_point) {
-            dp.insert((next_point, *dir, 0), *heat_value + heat_start);
-            let next_node = QueueNode::new(
-                next_point, 
-                end_point, 
-                *dir, 
-                0, 
-                *heat_value + heat_start,
-                *heat_value + heat_start,
-                path.clone()
-            );
-            queue.push(next_node);
-        };
-    });
+impl CrucibleConfig {
+    fn regular(start: Point, end: Point) -> Self {
+        Self { min_straight: 1, max_straight: 3, start, end, turn_left: true, turn_right: true }
+    }
 
-   //dbg!(&queue);
+    fn ultra(start: Point, end: Point) -> Self {
+        Self { min_straight: 4, max_straight: 10, start, end, turn_left: true, turn_right: true }
+    }
+
+    /// Dense index of the (block, direction, run) state.
+    fn state(&self, block: usize, dir: Direction, run: usize) -> usize {
+        (block * DIRECTIONS.len() + dir.index()) * (self.max_straight + 1) + run
+    }
+
+    fn block_and_direction(&self, state: usize) -> (usize, Direction) {
+        let block_dir = state / (self.max_straight + 1);
+        (block_dir / DIRECTIONS.len(), DIRECTIONS[block_dir % DIRECTIONS.len()])
+    }
 
-    queue
+    /// Directions and resulting runs the crucible may move in next.
+    fn next_moves(&self, dir: Direction, run: usize) -> Vec<(Direction, usize)> {
+        let mut moves = Vec::with_capacity(3);
+        if run < self.max_straight {
+            moves.push((dir, run + 1));
+        }
+        if run >= self.min_straight.max(1) {
+            if self.turn_left {
+                moves.push((dir.turn_left(), 1));
+            }
+            if self.turn_right {
+                moves.push((dir.turn_right(), 1));
+            }
+        }
+        moves
+    }
+
+    fn is_end(&self, point: Point, run: usize) -> bool {
+        point == self.end && run >= self.min_straight
+    }
+
+    /// Rebuilds a route from its states, ordered from start to end.
+    fn route(&self, city: &City, states: &[usize]) -> Route {
+        let (start, _) = self.block_and_direction(states[0]);
+        let moves: Vec<(Point, Direction)> = states[1..].iter()
+            .map(|&state| {
+                let (block, dir) = self.block_and_direction(state);
+                (city.point(block), dir)
+            })
+            .collect();
+        let heat_loss = moves.iter()
+            .map(|&(point, _)| city.heat[city.index(point).unwrap()] as u32)
+            .sum();
+
+        Route { start: city.point(start), moves, heat_loss }
+    }
 }
 
-fn part_1(
-    contents: &String,
-) -> u16 {
-    let grid = get_grid_and_dp(contents);
-    let start_point = Point::new(0, 0);
-    let grid_size = (grid.len() as f64).sqrt() as i32;
-    let end_point = Point::new(grid_size - 1, grid_size - 1);
-    let mut dp = HashMap::new();
-
-    let mut queue = init_queue(&grid, start_point, &end_point, &mut dp);
-      
-    let min = min_heat_loss(
-        &grid, 
-        &end_point, 
-        &mut queue,  
-        &mut dp
-    );
+/// A straight run of a route and the heat lost along it.
+#[derive(Debug, PartialEq)]
+struct RouteSegment {
+    direction: Direction,
+    length: usize,
+    heat_loss: u32,
+}
 
-    min - grid.get(&start_point).unwrap()
+/// A route as the block entered by every move and the direction of that move.
+#[derive(Debug, PartialEq)]
+struct Route {
+    start: Point,
+    moves: Vec<(Point, Direction)>,
+    heat_loss: u32,
 }
 
-fn min_heat_loss_part_2(
-    grid: &HashMap<Point, u16>,
-    end_point: &Point,
-    queue: &mut MinHeap<QueueNode>,
-    dp: &mut HashMap<(Point, Direction, u8), u16>,
-) -> u16 {
-    loop {
-        let node = match queue.pop() {
-            Some(node) => node,
-            None => break,
-        };
+impl Route {
+    fn segments(&self, city: &City) -> Vec<RouteSegment> {
+        let mut segments: Vec<RouteSegment> = Vec::new();
+
+        for &(point, dir) in &self.moves {
+            let heat = city.heat[city.index(point).unwrap()] as u32;
+            match segments.last_mut() {
+                Some(segment) if segment.direction == dir => {
+                    segment.length += 1;
+                    segment.heat_loss += heat;
+                }
+                _ => segments.push(RouteSegment { direction: dir, length: 1, heat_loss: heat }),
+            }
+        }
+
+        segments
+    }
+
+    fn turns(&self, city: &City) -> usize {
+        self.segments(city).len().saturating_sub(1)
+    }
+
+    fn longest_run(&self, city: &City) -> usize {
+        self.segments(city).iter().map(|segment| segment.length).max().unwrap_or(0)
+    }
 
-        if node.point == *end_point {
-            return node.heat_value;
+    /// The city with every block the route enters replaced by an arrow of the move.
+    fn render(&self, city: &City) -> String {
+        let mut rows: Vec<Vec<char>> = city.heat.chunks(city.width)
+            .map(|row| row.iter().map(|&heat| (b'0' + heat) as char).collect())
+            .collect();
+
+        for &(point, dir) in &self.moves {
+            rows[point.y as usize][point.x as usize] = match dir {
+                Direction::Up => '^',
+                Direction::Down => 'v',
+                Direction::Left => '<',
+                Direction::Right => '>',
+            };
         }
 
-        let dirs = if node.steps < 3 {
-            vec![node.direction]
-        } else if node.steps < 9 {
-            node.direction.no_return_dirs()
-        } else {
-            node.direction.turn_dirs()
+        rows.into_iter()
+            .map(|row| row.into_iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Finished A* search over (block, direction, run) states stored in dense arrays.
+/// A state's run is how many blocks the crucible has moved in its direction so far.
+struct CrucibleSearch {
+    config: CrucibleConfig,
+    heat_loss: Vec<u32>,
+    parent: Vec<usize>,
+    end_state: Option<usize>,
+}
+
+impl CrucibleSearch {
+    fn run(
+        city: &City,
+        config: &CrucibleConfig,
+    ) -> Self {
+        let num_states = city.heat.len() * DIRECTIONS.len() * (config.max_straight + 1);
+
+        let mut search = CrucibleSearch {
+            config: *config,
+            heat_loss: vec![u32::MAX; num_states],
+            parent: vec![usize::MAX; num_states],
+            end_state: None,
         };
 
-        for dir in dirs {
-            let next_point = node.point.add(&dir.to_point());
-        
-            let next_heat = match grid.get(&next_point) {
-                Some(heat) => heat,
-                None => continue,
-            };
-        
-            let heat = dp.get(&(node.point, node.direction, node.steps)).unwrap() + next_heat;
-            let steps = if dir == node.direction {
-                node.steps + 1
-            } else {
-                0
-            };
+        let mut queue = MinHeap::new();
+        if let Some(start) = city.index(config.start) {
+            // A run of 0 lets the crucible leave the start in any direction, but not turn
+            for dir in DIRECTIONS {
+                search.heat_loss[config.state(start, dir, 0)] = 0;
+                queue.push((config.start.get_distance(&config.end), 0, start, dir, 0));
+            }
+        }
 
-            if !dp.contains_key(&(next_point, dir, steps)) || heat < *dp.get(&(next_point, dir, steps)).unwrap() {
-                dp.insert((next_point, dir, steps), heat);
-            } else {
+        while let Some((_, heat_loss, block, dir, run)) = queue.pop() {
+            let current = config.state(block, dir, run);
+            if heat_loss > search.heat_loss[current] {
                 continue;
             }
-        
-            let next_node = QueueNode::new(
-                next_point,
-                end_point,
-                dir,
-                steps,
-                heat,
-                heat,
-                node.path.clone(),
-            );
-        
-            queue.push(next_node);
+
+            let point = city.point(block);
+            if config.is_end(point, run) {
+                search.end_state = Some(current);
+                break;
+            }
+
+            for (next_dir, next_run) in config.next_moves(dir, run) {
+                let next_point = point.add(&next_dir.to_point());
+                let Some(next_block) = city.index(next_point) else {
+                    continue;
+                };
+
+                let next = config.state(next_block, next_dir, next_run);
+                let next_heat_loss = heat_loss + city.heat[next_block] as u32;
+                if next_heat_loss < search.heat_loss[next] {
+                    search.heat_loss[next] = next_heat_loss;
+                    search.parent[next] = current;
+                    queue.push((
+                        next_heat_loss + next_point.get_distance(&config.end),
+                        next_heat_loss,
+                        next_block,
+                        next_dir,
+                        next_run,
+                    ));
+                }
+            }
         }
+
+        search
+    }
+
+    fn min_heat_loss(&self) -> Option<u32> {
+        self.end_state.map(|end_state| self.heat_loss[end_state])
+    }
+
+    /// The cheapest route from start to end, rebuilt from parent pointers.
+    fn route(
+        &self,
+        city: &City,
+    ) -> Option<Route> {
+        let mut current = self.end_state?;
+        let mut states = vec![current];
+
+        while self.parent[current] != usize::MAX {
+            current = self.parent[current];
+            states.push(current);
+        }
+
+        states.reverse();
+        Some(self.config.route(city, &states))
     }
-    unreachable!()
 }
 
-fn part_2(
-    contents: &String,
-) -> u16 {
-    let grid = get_grid_and_dp(contents);
-    let start_point = Point::new(0, 0);
-    let grid_size = (grid.len() as f64).sqrt() as i32;
-    let end_point = Point::new(grid_size - 1, grid_size - 1);
-    let mut dp = HashMap::new();
-
-    let mut queue = init_queue(&grid, start_point, &end_point, &mut dp);
-      
-    let min = min_heat_loss_part_2(
-        &grid, 
-        &end_point, 
-        &mut queue,  
-        &mut dp
+/// The `k` cheapest distinct routes from start to end, cheapest first. Every state may be
+/// settled up to `k` times, each time with its own chain of previous states.
+fn k_cheapest_routes(
+    city: &City,
+    config: &CrucibleConfig,
+    k: usize,
+) -> Vec<Route> {
+    let num_states = city.heat.len() * DIRECTIONS.len() * (config.max_straight + 1);
+    let mut settled = vec![0; num_states];
+    // (state, index of the previous entry)
+    let mut entries: Vec<(usize, usize)> = Vec::new();
+    let mut routes = Vec::new();
+
+    let mut queue = MinHeap::new();
+    if let Some(start) = city.index(config.start) {
+        for dir in DIRECTIONS {
+            entries.push((config.state(start, dir, 0), usize::MAX));
+            queue.push((config.start.get_distance(&config.end), 0, entries.len() - 1));
+        }
+    }
+
+    while let Some((_, heat_loss, entry)) = queue.pop() {
+        let current = entries[entry].0;
+        if settled[current] == k {
+            continue;
+        }
+        settled[current] += 1;
+
+        let (block, dir) = config.block_and_direction(current);
+        let run = current % (config.max_straight + 1);
+        let point = city.point(block);
+
+        if config.is_end(point, run) {
+            let mut states = Vec::new();
+            let mut previous = entry;
+            while previous != usize::MAX {
+                states.push(entries[previous].0);
+                previous = entries[previous].1;
+            }
+            states.reverse();
+
+            routes.push(config.route(city, &states));
+            if routes.len() == k {
+                break;
+            }
+            continue;
+        }
+
+        for (next_dir, next_run) in config.next_moves(dir, run) {
+            let next_point = point.add(&next_dir.to_point());
+            let Some(next_block) = city.index(next_point) else {
+                continue;
+            };
+
+            let next = config.state(next_block, next_dir, next_run);
+            if settled[next] < k {
+                let next_heat_loss = heat_loss + city.heat[next_block] as u32;
+                entries.push((next, entry));
+                queue.push((next_heat_loss + next_point.get_distance(&config.end), next_heat_loss, entries.len() - 1));
+            }
+        }
+    }
+
+    routes
+}
+
+fn print_route(
+    city: &City,
+    route: &Route,
+) {
+    println!("{}", route.render(city));
+    for segment in route.segments(city) {
+        println!("  {:?} x{}: heat loss {}", segment.direction, segment.length, segment.heat_loss);
+    }
+    println!(
+        "Heat loss {}, {} turns, longest straight run {}",
+        route.heat_loss, route.turns(city), route.longest_run(city)
     );
+}
 
-    min - grid.get(&start_point).unwrap()
+fn part_1(
+    contents: &str,
+) -> Result<u32, CityError> {
+    let city = City::parse(contents)?;
+    let config = CrucibleConfig::regular(Point::new(0, 0), city.bottom_right());
+
+    CrucibleSearch::run(&city, &config).min_heat_loss().ok_or(CityError::NoRoute)
+}
+
+fn part_2(
+    contents: &str,
+) -> Result<u32, CityError> {
+    let city = City::parse(contents)?;
+    let config = CrucibleConfig::ultra(Point::new(0, 0), city.bottom_right());
+
+    CrucibleSearch::run(&city, &config).min_heat_loss().ok_or(CityError::NoRoute)
 }
 
 #[cfg(test)]
@@ -364,7 +534,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_1(&contents), 102);
+        assert_eq!(part_1(&contents), Ok(102));
     }
     
     #[test]
@@ -373,6 +543,60 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 94);
+        assert_eq!(part_2(&contents), Ok(94));
+    }
+
+    #[test]
+    fn test_crucible_config() {
+        let contents = "111111111111\n999999999991\n999999999991\n999999999991\n999999999991";
+
+        assert_eq!(part_2(contents), Ok(71));
+        assert_eq!(part_1("5"), Err(CityError::NoRoute));
+        assert_eq!(part_2("5"), Err(CityError::NoRoute));
+        assert_eq!(part_1("12\n30"), Err(CityError::InvalidBlock { row: 1, column: 1, found: '0' }));
+        assert_eq!(part_1("12\n3x"), Err(CityError::InvalidBlock { row: 1, column: 1, found: 'x' }));
+        assert_eq!(part_1("12\n3"), Err(CityError::RaggedRow(1)));
+
+        let city = City::parse(contents).unwrap();
+        let right_only = CrucibleConfig {
+            min_straight: 1,
+            max_straight: 12,
+            start: Point::new(0, 0),
+            end: city.bottom_right(),
+            turn_left: false,
+            turn_right: true,
+        };
+
+        let search = CrucibleSearch::run(&city, &right_only);
+        assert_eq!(search.min_heat_loss(), Some(15));
+
+        let route = search.route(&city).unwrap();
+        assert_eq!(route.start, Point::new(0, 0));
+        assert_eq!(route.moves.last(), Some(&(city.bottom_right(), Direction::Down)));
+        assert_eq!(route.moves.len(), 15);
+
+        let stuck = CrucibleConfig { turn_right: false, ..right_only };
+        assert_eq!(CrucibleSearch::run(&city, &stuck).min_heat_loss(), None);
+    }
+
+    #[test]
+    fn test_route_breakdown() {
+        let file_path = "test_input.txt";
+
+        let contents = fs::read_to_string(file_path).unwrap();
+
+        let city = City::parse(&contents).unwrap();
+        let config = CrucibleConfig::ultra(Point::new(0, 0), city.bottom_right());
+        let route = CrucibleSearch::run(&city, &config).route(&city).unwrap();
+
+        assert_eq!(route.heat_loss, 94);
+        assert_eq!(route.segments(&city).iter().map(|segment| segment.heat_loss).sum::<u32>(), 94);
+        assert!(route.segments(&city).iter().all(|segment| (4..=10).contains(&segment.length)));
+        assert_eq!(route.render(&city).lines().next().unwrap(), "2>>>>>>>>1323");
+
+        let routes = k_cheapest_routes(&city, &CrucibleConfig::regular(Point::new(0, 0), city.bottom_right()), 3);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[0].heat_loss, 102);
+        assert!(routes.windows(2).all(|pair| pair[0].heat_loss <= pair[1].heat_loss && pair[0] != pair[1]));
     }
 }
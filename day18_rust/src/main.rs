@@ -1,49 +1,59 @@
-use std::fs;
+use std::{env, fmt, fs};
 
 fn main() {
     let file_path = "input.txt";
 
     let contents = fs::read_to_string(file_path).unwrap();
 
-    println!("Min part 1: {}", part_1(&contents));
-    println!("Min part 2: {}", part_2(&contents));
+    match part_1(&contents) {
+        Ok(volume) => println!("Min part 1: {}", volume),
+        Err(err) => println!("Part 1 failed: {}", err),
+    }
+    match part_2(&contents) {
+        Ok(volume) => println!("Min part 2: {}", volume),
+        Err(err) => println!("Part 2 failed: {}", err),
+    }
+
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().any(|arg| arg == "--validate") {
+        for (reading, plan) in [("Part 1", DigPlan::parse(&contents)), ("Part 2", DigPlan::parse_hex(&contents))] {
+            match plan.and_then(|plan| plan.validate()) {
+                Ok(()) => println!("{} dig plan is valid", reading),
+                Err(err) => println!("{} dig plan is invalid: {}", reading, err),
+            }
+        }
+    }
+
+    if let Some(path) = args.iter().position(|arg| arg == "--svg").and_then(|i| args.get(i + 1)) {
+        match DigPlan::parse(&contents).and_then(|plan| plan.validate().map(|_| plan)) {
+            Ok(plan) => fs::write(path, plan.to_svg()).unwrap(),
+            Err(err) => println!("SVG export failed: {}", err),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Point {
-    x: i32,
-    y: i32,
+    x: i128,
+    y: i128,
 }
 
 impl Point {
-    fn new(x: i32, y: i32) -> Self {
+    fn new(x: i128, y: i128) -> Self {
         Self { x, y }
     }
 
-    fn dig<F>(&mut self, direction: &Direction, count: u32, mut func: F)
-    where
-        F: FnMut(&mut Self),
-    {
-    let mut range = match direction {
-        Direction::Up => (self.y - count as i32..self.y).collect::<Vec<_>>(),
-        Direction::Down => (self.y + 1..self.y + count as i32 + 1).collect::<Vec<_>>(),
-        Direction::Left => (self.x - count as i32..self.x).collect::<Vec<_>>(),
-        Direction::Right => (self.x + 1..self.x + count as i32 + 1).collect::<Vec<_>>(),
-    };
-    
-    if matches!(direction, Direction::Up | Direction::Left) {
-        range.reverse();
-    }
-    
-    range.iter().for_each(|&i| {
+    /// The corner reached by digging `count` metres in `direction`.
+    fn dig(&self, direction: &Direction, count: u64) -> Self {
+        let count = count as i128;
         match direction {
-            Direction::Up | Direction::Down => self.y = i,
-            Direction::Left | Direction::Right => self.x = i,
+            Direction::Up => Self::new(self.x, self.y - count),
+            Direction::Down => Self::new(self.x, self.y + count),
+            Direction::Left => Self::new(self.x - count, self.y),
+            Direction::Right => Self::new(self.x + count, self.y),
         }
-    
-        func(self);
-    });
-}
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
@@ -54,92 +64,295 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum PlanError {
+    InvalidLine { line: usize, reason: String },
+    Empty,
+    NotClosed(Point),
+    SelfIntersecting { first: usize, second: usize },
+    Overflow,
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanError::InvalidLine { line, reason } => write!(f, "line {}: {}", line, reason),
+            PlanError::Empty => write!(f, "dig plan is empty"),
+            PlanError::NotClosed(end) => {
+                write!(f, "trench ends at ({}, {}) instead of the origin", end.x, end.y)
+            }
+            PlanError::SelfIntersecting { first, second } => {
+                write!(f, "trench segments {} and {} cross", first + 1, second + 1)
+            }
+            PlanError::Overflow => write!(f, "lagoon volume does not fit in 128 bits"),
+        }
+    }
+}
+
+/// One dig instruction and the colour its trench is painted, as 0xrrggbb.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Instruction {
+    direction: Direction,
+    count: u64,
+    colour: u32,
+}
+
+fn parse_colour(
+    field: &str,
+) -> Result<u32, String> {
+    field.strip_prefix("(#")
+        .and_then(|hex| hex.strip_suffix(')'))
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| format!("invalid colour {:?}", field))
+}
+
 fn parse_line(
     line: &str,
-) -> (Direction, u8) {
+) -> Result<Instruction, String> {
     let split = line.split_whitespace().collect::<Vec<_>>();
+    let [direction, count, colour] = split[..] else {
+        return Err(format!("expected 3 fields, found {}", split.len()));
+    };
 
-    let direction = match split[0] {
+    let direction = match direction {
         "U" => Direction::Up,
         "D" => Direction::Down,
         "L" => Direction::Left,
         "R" => Direction::Right,
-        _ => panic!("Invalid direction"),
+        _ => return Err(format!("invalid direction {:?}", direction)),
     };
 
-    let count = split[1].parse::<u8>().unwrap();
+    let count = count.parse::<u64>()
+        .ok()
+        .filter(|&count| count > 0)
+        .ok_or_else(|| format!("invalid count {:?}", count))?;
 
-    (direction, count)
+    Ok(Instruction { direction, count, colour: parse_colour(colour)? })
 }
 
-fn calculate_area(points: &Vec<Point>) -> i64 {
-    let mut area = 0.0;
-    let n = points.len();
+fn parse_line_part_2(
+    line: &str,
+) -> Result<Instruction, String> {
+    let instruction = parse_line(line)?;
+
+    // The six hex digits are the count in five digits followed by the direction
+    let direction = match instruction.colour & 0xf {
+        3 => Direction::Up,
+        1 => Direction::Down,
+        2 => Direction::Left,
+        0 => Direction::Right,
+        digit => return Err(format!("invalid direction digit {:x}", digit)),
+    };
 
-    // Calculate area using the Shoelace formula
-    for i in 0..n {
-        let j = (i + 1) % n;
-        area += (points[i].x as f64 * points[j].y as f64) - (points[j].x as f64 * points[i].y as f64);
+    let count = (instruction.colour >> 4) as u64;
+    if count == 0 {
+        return Err("invalid count 0".to_string());
     }
 
-    (area.abs() / 2.0) as i64
+    Ok(Instruction { direction, count, ..instruction })
 }
 
-fn part_1(
-    contents: &String,
-) -> i64 {
-    let mut edge = Vec::new();
+/// A dig plan starting at the origin, one trench segment per instruction.
+#[derive(Debug)]
+struct DigPlan {
+    instructions: Vec<Instruction>,
+}
 
-    contents.lines()
-        .map(|line| parse_line(line))
-        .fold(Point::new(0, 0), |mut point, (direction, count)| {
-            point.dig(&direction, count as u32, |point| {
-                edge.push(point.clone());
-            });
+impl DigPlan {
+    fn parse(contents: &str) -> Result<Self, PlanError> {
+        Self::parse_with(contents, parse_line)
+    }
 
-            point
-        });
+    /// Reads each instruction from its colour, as in part 2.
+    fn parse_hex(contents: &str) -> Result<Self, PlanError> {
+        Self::parse_with(contents, parse_line_part_2)
+    }
 
-    (calculate_area(&edge) - edge.len() as i64/2 + 1) + edge.len() as i64
-}
+    fn parse_with(
+        contents: &str,
+        parse: fn(&str) -> Result<Instruction, String>,
+    ) -> Result<Self, PlanError> {
+        let instructions = read_instructions(contents, parse).collect::<Result<Vec<_>, _>>()?;
 
-fn parse_line_part_2(
-    line: &str,
-) -> (Direction, u32) {
-    let split = line.split_whitespace().collect::<Vec<_>>();
+        Ok(Self { instructions })
+    }
 
-    let trimmed = split[2].trim_matches(|c| c == '(' || c == ')');
+    /// Every corner of the trench, starting and ending at the origin for a closed plan.
+    fn corners(&self) -> Vec<Point> {
+        let mut corners = vec![Point::new(0, 0)];
+        for instruction in &self.instructions {
+            let corner = corners[corners.len() - 1].dig(&instruction.direction, instruction.count);
+            corners.push(corner);
+        }
+        corners
+    }
 
-    let direction = match trimmed.chars().last().unwrap() {
-        '3' => Direction::Up,
-        '1' => Direction::Down,
-        '2' => Direction::Left,
-        '0' => Direction::Right,
-        _ => panic!("Invalid direction"),
-    };
+    /// Checks the trench returns to the origin and no two segments touch except where
+    /// consecutive segments meet at a corner. This compares every pair of segments, so
+    /// it is kept apart from the streaming volume calculation.
+    fn validate(&self) -> Result<(), PlanError> {
+        let corners = self.corners();
+        let n = self.instructions.len();
 
-    let count_string = &trimmed[1..trimmed.len()-1];
-    let count = u32::from_str_radix(count_string, 16).unwrap();
+        if n == 0 {
+            return Err(PlanError::Empty);
+        }
+        if corners[n] != Point::new(0, 0) {
+            return Err(PlanError::NotClosed(corners[n]));
+        }
+
+        let bounds: Vec<(i128, i128, i128, i128)> = corners.windows(2)
+            .map(|pair| (
+                pair[0].x.min(pair[1].x),
+                pair[0].x.max(pair[1].x),
+                pair[0].y.min(pair[1].y),
+                pair[0].y.max(pair[1].y),
+            ))
+            .collect();
+
+        for first in 0..n {
+            for second in first + 1..n {
+                let adjacent = second == first + 1 || (first == 0 && second == n - 1);
+
+                let crosses = if adjacent && n > 2 {
+                    // Consecutive segments share a corner, so they only overlap by doubling back
+                    let (before, after) = if second == first + 1 { (first, second) } else { (second, first) };
+                    self.instructions[after].direction == self.instructions[before].direction.opposite()
+                } else {
+                    let (x1, x2, y1, y2) = bounds[first];
+                    let (u1, u2, v1, v2) = bounds[second];
+                    x1 <= u2 && u1 <= x2 && y1 <= v2 && v1 <= y2
+                };
+
+                if crosses {
+                    return Err(PlanError::SelfIntersecting { first, second });
+                }
+            }
+        }
+
+        Ok(())
+    }
 
-    (direction, count)
+    /// The lagoon as SVG: the interior filled, each trench segment stroked in its colour.
+    fn to_svg(&self) -> String {
+        let corners = self.corners();
+        let min_x = corners.iter().map(|corner| corner.x).min().unwrap_or(0);
+        let max_x = corners.iter().map(|corner| corner.x).max().unwrap_or(0);
+        let min_y = corners.iter().map(|corner| corner.y).min().unwrap_or(0);
+        let max_y = corners.iter().map(|corner| corner.y).max().unwrap_or(0);
+
+        // Trenches are a metre wide, widened on huge plans so they stay visible
+        let stroke_width = ((max_x - min_x).max(max_y - min_y) / 400).max(1);
+        let margin = stroke_width as f64 / 2.0;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min_x as f64 - margin,
+            min_y as f64 - margin,
+            (max_x - min_x) as f64 + 2.0 * margin,
+            (max_y - min_y) as f64 + 2.0 * margin,
+        );
+
+        let points: Vec<String> = corners.iter().map(|corner| format!("{},{}", corner.x, corner.y)).collect();
+        svg += &format!("  <polygon points=\"{}\" fill=\"#3b2f2f\"/>\n", points.join(" "));
+
+        for (pair, instruction) in corners.windows(2).zip(&self.instructions) {
+            svg += &format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#{:06x}\" stroke-width=\"{}\" stroke-linecap=\"square\"/>\n",
+                pair[0].x, pair[0].y, pair[1].x, pair[1].y, instruction.colour, stroke_width,
+            );
+        }
+
+        svg += "</svg>\n";
+        svg
+    }
 }
 
-fn part_2(
-    contents: &String,
-) -> i64 {
-    let mut edge = Vec::new();
+/// Cubic metres of lava held by the trench and its interior, from the corners alone.
+/// The shoelace sum gives the polygon's area through the centres of the trench cells,
+/// and Pick's theorem adds the half of the trench lying outside it: area + perimeter / 2 + 1.
+/// Returns `None` if any intermediate value overflows i128.
+fn lagoon_volume<I>(instructions: I) -> Option<i128>
+where
+    I: IntoIterator<Item = (Direction, u64)>,
+{
+    let mut corner = Point::new(0, 0);
+    let mut twice_area: i128 = 0;
+    let mut perimeter: i128 = 0;
+
+    for (direction, count) in instructions {
+        let count = count as i128;
+        let next = match direction {
+            Direction::Up => Point::new(corner.x, corner.y.checked_sub(count)?),
+            Direction::Down => Point::new(corner.x, corner.y.checked_add(count)?),
+            Direction::Left => Point::new(corner.x.checked_sub(count)?, corner.y),
+            Direction::Right => Point::new(corner.x.checked_add(count)?, corner.y),
+        };
+
+        let cross = corner.x.checked_mul(next.y)?.checked_sub(next.x.checked_mul(corner.y)?)?;
+        twice_area = twice_area.checked_add(cross)?;
+        perimeter = perimeter.checked_add(count)?;
+        corner = next;
+    }
+
+    Some(twice_area.checked_abs()?.checked_add(perimeter)? / 2 + 1)
+}
 
+/// Parses instructions lazily, skipping blank lines and numbering errors from line 1.
+fn read_instructions(
+    contents: &str,
+    parse: fn(&str) -> Result<Instruction, String>,
+) -> impl Iterator<Item = Result<Instruction, PlanError>> + '_ {
     contents.lines()
-        .map(|line| parse_line_part_2(line))
-        .fold(Point::new(0, 0), |mut point, (direction, count)| {
-            point.dig(&direction, count, |point| {
-                edge.push(point.clone());
-            });
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(move |(i, line)| parse(line).map_err(|reason| PlanError::InvalidLine { line: i + 1, reason }))
+}
+
+/// Feeds instructions straight into `lagoon_volume` without storing them, stopping at
+/// the first line that fails to parse.
+fn streamed_volume(
+    contents: &str,
+    parse: fn(&str) -> Result<Instruction, String>,
+) -> Result<i128, PlanError> {
+    let mut error = None;
+
+    let volume = lagoon_volume(read_instructions(contents, parse).map_while(|instruction| match instruction {
+        Ok(instruction) => Some((instruction.direction, instruction.count)),
+        Err(err) => {
+            error = Some(err);
+            None
+        }
+    }));
+
+    match error {
+        Some(err) => Err(err),
+        None => volume.ok_or(PlanError::Overflow),
+    }
+}
 
-            point
-        });
+fn part_1(
+    contents: &str,
+) -> Result<i128, PlanError> {
+    streamed_volume(contents, parse_line)
+}
 
-    (calculate_area(&edge) - edge.len() as i64/2 + 1) + edge.len() as i64
+fn part_2(
+    contents: &str,
+) -> Result<i128, PlanError> {
+    streamed_volume(contents, parse_line_part_2)
 }
 
 #[cfg(test)]
@@ -152,7 +365,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_1(&contents), 62);
+        assert_eq!(part_1(&contents), Ok(62));
     }
     
     #[test]
@@ -161,6 +374,54 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 952408144115);
+        assert_eq!(part_2(&contents), Ok(952408144115));
+    }
+
+    #[test]
+    fn test_lagoon_volume() {
+        assert_eq!(lagoon_volume([
+            (Direction::Right, 2),
+            (Direction::Down, 2),
+            (Direction::Left, 2),
+            (Direction::Up, 2),
+        ]), Some(9));
+
+        let side = 1_000_000_000_000_000;
+        let square = [Direction::Right, Direction::Down, Direction::Left, Direction::Up]
+            .map(|direction| (direction, side));
+
+        assert_eq!(lagoon_volume(square), Some((side as i128 + 1) * (side as i128 + 1)));
+
+        let huge = "R 18446744073709551615 (#000000)\nD 18446744073709551615 (#000000)\n\
+            L 18446744073709551615 (#000000)\nU 18446744073709551615 (#000000)";
+        assert_eq!(part_1(huge), Err(PlanError::Overflow));
+    }
+
+    #[test]
+    fn test_plan_validation() {
+        assert_eq!(
+            DigPlan::parse("R 2 (#ff0000)\nD 2 (#00ff00)\nL 2 (#0000ff)").unwrap().validate(),
+            Err(PlanError::NotClosed(Point::new(0, 2)))
+        );
+
+        // A figure of eight crossing itself at (2, 2)
+        let crossing = "R 2 (#000000)\nD 4 (#000000)\nR 2 (#000000)\nU 2 (#000000)\nL 4 (#000000)\nU 2 (#000000)";
+        assert_eq!(
+            DigPlan::parse(crossing).unwrap().validate(),
+            Err(PlanError::SelfIntersecting { first: 1, second: 4 })
+        );
+
+        assert_eq!(
+            DigPlan::parse("R 2 (#000000)\nL 2 (#000000)").unwrap().validate(),
+            Err(PlanError::SelfIntersecting { first: 0, second: 1 })
+        );
+        assert_eq!(
+            part_1("R 2 (#000000)\nX 2 (#000000)").unwrap_err().to_string(),
+            "line 2: invalid direction \"X\""
+        );
+
+        let svg = DigPlan::parse("R 2 (#ff0000)\nD 2 (#00ff00)\nL 2 (#0000ff)\nU 2 (#123abc)").unwrap().to_svg();
+        assert!(svg.contains("<polygon points=\"0,0 2,0 2,2 0,2 0,0\""));
+        assert!(svg.contains("stroke=\"#123abc\""));
     }
 }
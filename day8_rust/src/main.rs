@@ -1,29 +1,117 @@
-use std::{fs, collections::{HashSet, HashMap}};
+use std::{env, fmt, fs, collections::{HashMap, HashSet, VecDeque}};
 use itertools::Itertools;
-use num_integer::lcm;
+use num_integer::Integer;
 
 fn main() {
     let file_path = "input.txt";
 
     let contents = fs::read_to_string(file_path).unwrap();
 
-    println!("Number of steps part 1: {}", part_1(&contents));
-    println!("Number of steps part 2: {}", part_2(&contents));
+    let network = parse_network(&contents);
+    for problem in validate_network(&network) {
+        println!("Invalid network: {}", problem);
+    }
+    println!("Strongly connected components: {}", strongly_connected_components(&network).len());
+
+    let args: Vec<String> = env::args().collect();
+    if let Some(dot_path) = args.iter().position(|arg| arg == "--dot").and_then(|i| args.get(i + 1)) {
+        fs::write(dot_path, network_to_dot(&network)).unwrap();
+    }
+
+    match part_1(&contents) {
+        Ok(steps) => println!("Number of steps part 1: {}", steps),
+        Err(err) => println!("Part 1 failed: {}", err),
+    }
+    match part_2(&contents) {
+        Ok(steps) => println!("Number of steps part 2: {}", steps),
+        Err(err) => println!("Part 2 failed: {}", err),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum NetworkError {
+    DanglingReference { node: String, target: String },
+    UnreachableEnd(String),
+    MissingNode(String),
+    StepLimitExceeded(usize),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::DanglingReference { node, target } => {
+                write!(f, "node {} points to undefined node {}", node, target)
+            }
+            NetworkError::UnreachableEnd(node) => {
+                write!(f, "start node {} cannot reach any node ending with 'Z'", node)
+            }
+            NetworkError::MissingNode(node) => write!(f, "node {} is not defined", node),
+            NetworkError::StepLimitExceeded(limit) => {
+                write!(f, "end node not reached within {} steps", limit)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum NavigationError {
+    InvalidNetwork(NetworkError),
+    NoStartNodes,
+    NeverReachesEnd(String),
+    NoCommonStep,
+    Overflow,
+}
+
+impl fmt::Display for NavigationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NavigationError::InvalidNetwork(err) => write!(f, "{}", err),
+            NavigationError::NoStartNodes => write!(f, "no node ends with 'A'"),
+            NavigationError::NeverReachesEnd(node) => {
+                write!(f, "ghost starting at {} never reaches a node ending with 'Z'", node)
+            }
+            NavigationError::NoCommonStep => {
+                write!(f, "ghosts never stand on nodes ending with 'Z' at the same step")
+            }
+            NavigationError::Overflow => write!(f, "the common step does not fit in 128 bits"),
+        }
+    }
+}
+
+/// Walk of a single ghost, keyed on (node, instruction index). After `tail` steps the
+/// walk repeats every `period` steps; `hits` holds every step in `0..tail + period`
+/// that lands on a node ending with 'Z'.
+#[derive(Debug, PartialEq)]
+struct GhostCycle {
+    tail: usize,
+    period: usize,
+    hits: Vec<usize>,
+}
+
+impl GhostCycle {
+    fn is_hit(&self, step: usize) -> bool {
+        let offset = if step < self.tail {
+            step
+        } else {
+            self.tail + (step - self.tail) % self.period
+        };
+        self.hits.contains(&offset)
+    }
+
+    fn cycle_hits(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hits.iter().copied().filter(|&hit| hit >= self.tail)
+    }
 }
 
 fn parse_directions(raw_directions: &str) -> Vec<char> {
     raw_directions.chars().collect()
 }
 
-fn get_directions(directions: &Vec<char>, step: usize) -> bool {
-    let mut index = step as usize;
-    while index >= directions.len() {
-        index = index - directions.len();
-    }
-    directions[index] == 'R'
+fn get_directions(directions: &[char], step: usize) -> bool {
+    directions[step % directions.len()] == 'R'
 }
 
-fn parse_network(contents: &String) -> HashMap<&str, (&str, &str)> {
+fn parse_network(contents: &str) -> HashMap<&str, (&str, &str)> {
     let mut network = HashMap::new();
 
     contents.lines().skip(2).for_each(|line| {
@@ -40,63 +128,296 @@ fn parse_network(contents: &String) -> HashMap<&str, (&str, &str)> {
     network
 }
 
-fn part_1(contents: &String) -> usize {
-    let directions = parse_directions(contents.lines().next().unwrap());
+fn is_start(node: &str) -> bool {
+    node.ends_with('A')
+}
 
-    let network = parse_network(contents);
+fn is_end(node: &str) -> bool {
+    node.ends_with('Z')
+}
+
+/// Reports every reference to an undefined node and every start node from which no end
+/// node can be reached by any choice of left/right.
+fn validate_network(network: &HashMap<&str, (&str, &str)>) -> Vec<NetworkError> {
+    let mut problems = Vec::new();
+
+    for (&node, &(left, right)) in network.iter().sorted() {
+        for target in [left, right].into_iter().dedup() {
+            if !network.contains_key(target) {
+                problems.push(NetworkError::DanglingReference {
+                    node: node.to_string(),
+                    target: target.to_string(),
+                });
+            }
+        }
+    }
+
+    for &node in network.keys().filter(|&&key| is_start(key)).sorted() {
+        let mut visited = HashSet::from([node]);
+        let mut queue = VecDeque::from([node]);
+        let mut reaches_end = false;
+
+        while let Some(current_node) = queue.pop_front() {
+            if is_end(current_node) {
+                reaches_end = true;
+                break;
+            }
+            if let Some(&(left, right)) = network.get(current_node) {
+                for next_node in [left, right] {
+                    if visited.insert(next_node) {
+                        queue.push_back(next_node);
+                    }
+                }
+            }
+        }
+
+        if !reaches_end {
+            problems.push(NetworkError::UnreachableEnd(node.to_string()));
+        }
+    }
+
+    problems
+}
+
+/// Kosaraju's algorithm with explicit stacks. Components and their nodes are sorted.
+fn strongly_connected_components<'a>(network: &HashMap<&'a str, (&'a str, &'a str)>) -> Vec<Vec<&'a str>> {
+    let successors = |node: &str| -> Vec<&'a str> {
+        network.get(node)
+            .map(|&(left, right)| vec![left, right])
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|target| network.contains_key(target))
+            .collect()
+    };
+
+    let mut predecessors: HashMap<&str, Vec<&str>> = HashMap::new();
+    for &node in network.keys() {
+        for target in successors(node) {
+            predecessors.entry(target).or_default().push(node);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut finish_order = Vec::new();
+    for &root in network.keys().sorted() {
+        if !visited.insert(root) {
+            continue;
+        }
+        let mut stack = vec![(root, successors(root), 0)];
+        while let Some((node, targets, index)) = stack.last_mut() {
+            if let Some(&target) = targets.get(*index) {
+                *index += 1;
+                if visited.insert(target) {
+                    stack.push((target, successors(target), 0));
+                }
+            } else {
+                finish_order.push(*node);
+                stack.pop();
+            }
+        }
+    }
+
+    let mut assigned = HashSet::new();
+    let mut components = Vec::new();
+    for &root in finish_order.iter().rev() {
+        if !assigned.insert(root) {
+            continue;
+        }
+        let mut component = vec![root];
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            for &source in predecessors.get(node).into_iter().flatten() {
+                if assigned.insert(source) {
+                    component.push(source);
+                    stack.push(source);
+                }
+            }
+        }
+        component.sort();
+        components.push(component);
+    }
+
+    components.sort();
+    components
+}
+
+/// Graphviz export of the network, start nodes are filled green and end nodes red.
+fn network_to_dot(network: &HashMap<&str, (&str, &str)>) -> String {
+    let mut dot = String::from("digraph network {\n");
+
+    for &node in network.keys().sorted() {
+        if is_start(node) {
+            dot.push_str(&format!("    \"{}\" [style=filled, fillcolor=palegreen];\n", node));
+        } else if is_end(node) {
+            dot.push_str(&format!("    \"{}\" [style=filled, fillcolor=salmon];\n", node));
+        }
+    }
 
+    for (&node, &(left, right)) in network.iter().sorted() {
+        dot.push_str(&format!("    \"{}\" -> \"{}\" [label=\"L\"];\n", node, left));
+        dot.push_str(&format!("    \"{}\" -> \"{}\" [label=\"R\"];\n", node, right));
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Follows the instructions from `start` until `is_target` holds, giving up after `max_steps`.
+fn walk(
+    network: &HashMap<&str, (&str, &str)>,
+    directions: &[char],
+    start: &str,
+    is_target: impl Fn(&str) -> bool,
+    max_steps: usize,
+) -> Result<usize, NetworkError> {
+    let mut current_node = start;
+
+    for steps in 0..=max_steps {
+        if is_target(current_node) {
+            return Ok(steps);
+        }
+
+        let current_node_result = network.get(current_node)
+            .ok_or_else(|| NetworkError::MissingNode(current_node.to_string()))?;
+        if get_directions(directions, steps) {
+            current_node = current_node_result.1;
+        } else {
+            current_node = current_node_result.0;
+        }
+    }
+
+    Err(NetworkError::StepLimitExceeded(max_steps))
+}
+
+fn find_cycle(start: &str, directions: &[char], network: &HashMap<&str, (&str, &str)>) -> GhostCycle {
+    let mut seen: HashMap<(&str, usize), usize> = HashMap::new();
+    let mut hits = Vec::new();
+
+    let mut current_node = start;
     let mut steps = 0;
 
-    let mut current_node = "AAA";
-    let mut current_node_result = network.get(current_node).unwrap();
+    loop {
+        let index = steps % directions.len();
+        if let Some(&tail) = seen.get(&(current_node, index)) {
+            return GhostCycle { tail, period: steps - tail, hits };
+        }
+        seen.insert((current_node, index), steps);
+
+        if is_end(current_node) {
+            hits.push(steps);
+        }
 
-    while current_node != "ZZZ" {
-        let direction = get_directions(&directions, steps);
-        if direction {
+        let current_node_result = network.get(current_node).unwrap();
+        if get_directions(directions, steps) {
             current_node = current_node_result.1;
         } else {
             current_node = current_node_result.0;
         }
-        current_node_result = network.get(current_node).unwrap();
         steps += 1;
     }
+}
+
+/// Generalised CRT: merges `x ≡ a1 (mod n1)` and `x ≡ a2 (mod n2)` into a single
+/// congruence modulo lcm(n1, n2), or `None` if the two are incompatible.
+fn combine_congruences(
+    (a1, n1): (i128, i128),
+    (a2, n2): (i128, i128),
+) -> Result<Option<(i128, i128)>, NavigationError> {
+    let egcd = n1.extended_gcd(&n2);
+    let gcd = egcd.gcd;
+    let difference = a2.checked_sub(a1).ok_or(NavigationError::Overflow)?;
+    if difference % gcd != 0 {
+        return Ok(None);
+    }
+
+    let lcm = (n1 / gcd).checked_mul(n2).ok_or(NavigationError::Overflow)?;
+    let k = (difference / gcd % (n2 / gcd)).checked_mul(egcd.x).ok_or(NavigationError::Overflow)? % (n2 / gcd);
+    let solution = n1.checked_mul(k)
+        .and_then(|offset| a1.checked_add(offset))
+        .ok_or(NavigationError::Overflow)?;
+
+    Ok(Some((solution.rem_euclid(lcm), lcm)))
+}
+
+fn first_common_step(cycles: &[GhostCycle]) -> Result<Option<u128>, NavigationError> {
+    // Below the longest tail some ghosts are not in their cycle yet, so check directly
+    let max_tail = cycles.iter().map(|cycle| cycle.tail).max().unwrap_or(0);
+    if let Some(step) = (0..max_tail).find(|&step| cycles.iter().all(|cycle| cycle.is_hit(step))) {
+        return Ok(Some(step as u128));
+    }
+
+    let max_tail = max_tail as i128;
 
-    steps
+    cycles.iter()
+        .map(|cycle| {
+            cycle.cycle_hits()
+                .map(|hit| (hit as i128 % cycle.period as i128, cycle.period as i128))
+                .collect::<Vec<_>>()
+        })
+        .multi_cartesian_product()
+        .map(|congruences| {
+            congruences.into_iter().try_fold(Some((0, 1)), |combined, congruence| match combined {
+                Some(combined) => combine_congruences(combined, congruence),
+                None => Ok(None),
+            })
+        })
+        .filter_map(Result::transpose)
+        .map(|congruence| {
+            let (residue, modulus) = congruence?;
+            if residue >= max_tail {
+                return Ok(residue);
+            }
+            // Smallest step past every tail, residue plus enough whole periods
+            (max_tail - residue + modulus - 1).checked_div(modulus)
+                .and_then(|periods| periods.checked_mul(modulus))
+                .and_then(|offset| residue.checked_add(offset))
+                .ok_or(NavigationError::Overflow)
+        })
+        .process_results(|steps| steps.min().map(|step| step as u128))
 }
 
-fn part_2(contents: &String) -> usize {
+fn part_1(contents: &str) -> Result<usize, NetworkError> {
     let directions = parse_directions(contents.lines().next().unwrap());
 
     let network = parse_network(contents);
 
-    let current_nodes: HashSet<&str> = network.keys()
-        .filter(|&key| key.ends_with("A"))
-        .copied()
-        .collect();
+    // Past this many steps some (node, instruction) pair has repeated, so ZZZ is unreachable
+    let max_steps = network.len() * directions.len();
 
-    let steps_vec: Vec<_> = current_nodes.iter()
-        .map(|&node| {
-            let mut steps = 0;
+    walk(&network, &directions, "AAA", |node| node == "ZZZ", max_steps)
+}
 
-            let mut current_node = node;
-            let mut current_node_result = network.get(current_node).unwrap();
+fn part_2(contents: &str) -> Result<u128, NavigationError> {
+    let directions = parse_directions(contents.lines().next().unwrap());
 
-            while !current_node.ends_with("Z") {
-                let direction = get_directions(&directions, steps);
-                if direction {
-                    current_node = current_node_result.1;
-                } else {
-                    current_node = current_node_result.0;
-                }
-                current_node_result = network.get(current_node).unwrap();
-                steps += 1;
-            }
+    let network = parse_network(contents);
 
-            steps
-        })
+    if let Some(problem) = validate_network(&network).into_iter().next() {
+        return Err(NavigationError::InvalidNetwork(problem));
+    }
+
+    let start_nodes: Vec<&str> = network.keys()
+        .filter(|&&key| is_start(key))
+        .copied()
+        .sorted()
+        .collect();
+
+    if start_nodes.is_empty() {
+        return Err(NavigationError::NoStartNodes);
+    }
+
+    let cycles: Vec<GhostCycle> = start_nodes.iter()
+        .map(|&node| find_cycle(node, &directions, &network))
         .collect();
 
-    steps_vec.iter().fold(1, |fold, &steps| lcm(fold, steps))
+    if let Some((&node, _)) = start_nodes.iter()
+        .zip(&cycles)
+        .find(|(_, cycle)| cycle.hits.is_empty())
+    {
+        return Err(NavigationError::NeverReachesEnd(node.to_string()));
+    }
+
+    first_common_step(&cycles)?.ok_or(NavigationError::NoCommonStep)
 }
 
 #[cfg(test)]
@@ -109,7 +430,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_1(&contents), 2);
+        assert_eq!(part_1(&contents), Ok(2));
     }
     
     #[test]
@@ -118,6 +439,92 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 6);
+        assert_eq!(part_2(&contents), Ok(6));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unaligned_cycles() {
+        // 11A reaches 11Z after 3 steps and then every 2 steps; 22A after 2 steps and then every 3
+        let contents = "L\n\n11A = (11B, XXX)\n11B = (11C, XXX)\n11C = (11Z, XXX)\n11Z = (11C, XXX)\n\
+            22A = (22B, XXX)\n22B = (22Z, XXX)\n22Z = (22C, XXX)\n22C = (22D, XXX)\n22D = (22Z, XXX)\n\
+            XXX = (XXX, XXX)";
+
+        let network = parse_network(contents);
+        assert_eq!(
+            find_cycle("11A", &['L'], &network),
+            GhostCycle { tail: 2, period: 2, hits: vec![3] }
+        );
+
+        assert_eq!(part_2(contents), Ok(5));
+    }
+
+    #[test]
+    fn test_no_common_step() {
+        let contents = "L\n\n11A = (11Z, XXX)\n11Z = (11B, XXX)\n11B = (11Z, XXX)\n\
+            22A = (22B, XXX)\n22B = (22Z, XXX)\n22Z = (22C, XXX)\n22C = (22Z, XXX)\n\
+            XXX = (XXX, XXX)";
+
+        assert_eq!(part_2(contents), Err(NavigationError::NoCommonStep));
+    }
+
+    #[test]
+    fn test_congruence_overflow() {
+        assert_eq!(combine_congruences((1, 4), (3, 6)), Ok(Some((9, 12))));
+        assert_eq!(combine_congruences((0, 4), (1, 6)), Ok(None));
+
+        // Consecutive moduli are coprime, so their lcm is about 2^140
+        let modulus = 1 << 70;
+        assert_eq!(
+            combine_congruences((0, modulus), (1, modulus - 1)),
+            Err(NavigationError::Overflow)
+        );
+        assert_eq!(
+            first_common_step(&[
+                GhostCycle { tail: 0, period: 1 << 62, hits: vec![0] },
+                GhostCycle { tail: 0, period: (1 << 62) - 1, hits: vec![1] },
+                GhostCycle { tail: 0, period: (1 << 62) - 3, hits: vec![2] },
+            ]),
+            Err(NavigationError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_validate_network() {
+        let contents = "LR\n\nAAA = (BBB, CCC)\nBBB = (BBB, BBB)\n11A = (11Z, 11Z)\n11Z = (11A, 11A)";
+
+        let network = parse_network(contents);
+
+        assert_eq!(validate_network(&network), vec![
+            NetworkError::DanglingReference { node: "AAA".to_string(), target: "CCC".to_string() },
+            NetworkError::UnreachableEnd("AAA".to_string()),
+        ]);
+        assert_eq!(part_1(contents), Err(NetworkError::StepLimitExceeded(8)));
+        assert_eq!(
+            part_2(contents),
+            Err(NavigationError::InvalidNetwork(NetworkError::DanglingReference {
+                node: "AAA".to_string(),
+                target: "CCC".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_strongly_connected_components() {
+        let contents = fs::read_to_string("test_input_part_2.txt").unwrap();
+
+        let network = parse_network(&contents);
+
+        assert_eq!(strongly_connected_components(&network), vec![
+            vec!["11A"],
+            vec!["11B", "11Z"],
+            vec!["22A"],
+            vec!["22B", "22C", "22Z"],
+            vec!["XXX"],
+        ]);
+
+        let dot = network_to_dot(&network);
+        assert!(dot.contains("    \"11A\" [style=filled, fillcolor=palegreen];"));
+        assert!(dot.contains("    \"22Z\" [style=filled, fillcolor=salmon];"));
+        assert!(dot.contains("    \"11B\" -> \"11Z\" [label=\"R\"];"));
+    }
+}
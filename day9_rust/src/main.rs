@@ -1,4 +1,4 @@
-use std::{fs, collections::BTreeMap};
+use std::{env, fmt, fs};
 use itertools::Itertools;
 use regex::Regex;
 
@@ -7,78 +7,247 @@ fn main() {
 
     let contents = fs::read_to_string(file_path).unwrap();
 
-    println!("Sum part 1: {}", part_1(&contents));
-    println!("Sum part 2: {}", part_2(&contents));
+    let args: Vec<String> = env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--at").and_then(|i| args.get(i + 1)) {
+        let index: i128 = index.parse().unwrap();
+        for (line_number, values) in parse_sequences(&contents).iter().enumerate() {
+            let report = NewtonSequence::new(values).and_then(|sequence| {
+                let polynomial = sequence.coefficients()?.iter()
+                    .enumerate()
+                    .map(|(power, coefficient)| format!("({})x^{}", coefficient, power))
+                    .join(" + ");
+                Ok(format!(
+                    "value at {}: {}, degree {}, p(x) = {}",
+                    index, sequence.value_at(index)?, sequence.degree(), polynomial
+                ))
+            });
+            match report {
+                Ok(report) => println!("Line {}: {}", line_number + 1, report),
+                Err(err) => println!("Line {}: {}", line_number + 1, err),
+            }
+        }
+    }
+
+    for (line_number, report) in analyse_sequences(&contents).iter().enumerate() {
+        match report {
+            Ok(report) => println!(
+                "Line {}: degree {}, next value {}, previous value {}",
+                line_number + 1, report.degree, report.next_value, report.previous_value
+            ),
+            Err(err) => println!("Line {}: {}", line_number + 1, err),
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--sums") {
+        match part_1(&contents) {
+            Ok(sum) => println!("Sum part 1: {}", sum),
+            Err(err) => println!("Part 1 failed: {}", err),
+        }
+        match part_2(&contents) {
+            Ok(sum) => println!("Sum part 2: {}", sum),
+            Err(err) => println!("Part 2 failed: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ExtrapolationError {
+    Overflow,
+    TooFewValues(usize),
+    NotPolynomial,
 }
 
-fn get_differences_map(
-    differences_vec: Vec<i64>, 
-    differences_map: &mut BTreeMap<u8, Vec<i64>>,
-    step: u8
-) {
-    if differences_vec.iter().all(|&x| x == 0) {
-        return;
+impl fmt::Display for ExtrapolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtrapolationError::Overflow => write!(f, "arithmetic overflow"),
+            ExtrapolationError::TooFewValues(count) => {
+                write!(f, "{} values are not enough to extrapolate, at least 2 are needed", count)
+            }
+            ExtrapolationError::NotPolynomial => {
+                write!(f, "differences never reach a row of zeros within the sequence length")
+            }
+        }
     }
+}
 
-    let differences_vec_new = differences_vec.iter()
-        .tuple_windows()
-        .map(|(a, b)| b - a)
-        .collect_vec();
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
 
-    differences_map.insert(step, differences_vec);
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
 
-    get_differences_map(differences_vec_new, differences_map, step+1)
+impl Rational {
+    fn new(numerator: i128, denominator: i128) -> Self {
+        let divisor = gcd(numerator, denominator).max(1) * denominator.signum();
+        Rational { numerator: numerator / divisor, denominator: denominator / divisor }
+    }
 }
 
-fn get_new_value(
-    differences_map: &BTreeMap<u8, Vec<i64>>,
-    is_first: bool
-) -> i64 {
-    differences_map.into_iter().rev()
-        .fold(0, |last_value, (_, differences_vec)| {
-            if is_first {
-                return differences_vec.last().unwrap() - last_value;
-            }
-            differences_vec.last().unwrap() + last_value
-        })
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
 }
 
-fn part_1(
-    contents: &String
-) -> i64 {
-    let reg = Regex::new(r"-?\d+").unwrap();
+/// A sequence stored as its Newton forward differences at index 0, so that
+/// `y(k) = sum over j of C(k, j) * leading_differences[j]` for any integer k.
+#[derive(Debug, PartialEq)]
+struct NewtonSequence {
+    leading_differences: Vec<i128>,
+}
+
+impl NewtonSequence {
+    fn new(values: &[i64]) -> Result<Self, ExtrapolationError> {
+        if values.len() < 2 {
+            return Err(ExtrapolationError::TooFewValues(values.len()));
+        }
+
+        let mut differences_vec = values.iter().map(|&value| value as i128).collect_vec();
+        let mut leading_differences = Vec::new();
+
+        while differences_vec.iter().any(|&x| x != 0) {
+            // A single non-zero difference leaves nothing to confirm the next row is zero
+            if differences_vec.len() == 1 {
+                return Err(ExtrapolationError::NotPolynomial);
+            }
+            leading_differences.push(differences_vec[0]);
+
+            differences_vec = differences_vec.iter()
+                .tuple_windows()
+                .map(|(a, b)| b.checked_sub(*a).ok_or(ExtrapolationError::Overflow))
+                .collect::<Result<_, _>>()?;
+        }
+
+        Ok(NewtonSequence { leading_differences })
+    }
+
+    fn degree(&self) -> usize {
+        self.leading_differences.len().saturating_sub(1)
+    }
+
+    /// Value at index `k`, where 0 is the first value of the sequence and negative
+    /// indices extrapolate backwards.
+    fn value_at(&self, k: i128) -> Result<i128, ExtrapolationError> {
+        let mut binomial: i128 = 1;
+        let mut value: i128 = 0;
+
+        for (j, &difference) in self.leading_differences.iter().enumerate() {
+            if j > 0 {
+                // C(k, j) = C(k, j - 1) * (k - j + 1) / j, the division is always exact
+                binomial = k.checked_sub(j as i128 - 1)
+                    .and_then(|factor| binomial.checked_mul(factor))
+                    .ok_or(ExtrapolationError::Overflow)? / j as i128;
+            }
+            value = binomial.checked_mul(difference)
+                .and_then(|term| value.checked_add(term))
+                .ok_or(ExtrapolationError::Overflow)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Coefficients of the interpolating polynomial, lowest power first.
+    fn coefficients(&self) -> Result<Vec<Rational>, ExtrapolationError> {
+        let degree = self.degree();
 
-    contents.lines().map(|line| {
-        let mut differences_map: BTreeMap<u8, Vec<i64>> = BTreeMap::new();
-        
-        let starting_vec = reg.captures_iter(line)
-            .map(|c| c.get(0).unwrap().as_str().parse::<i64>().unwrap())
-            .collect_vec();
+        // falling[i] holds the coefficient of x^i in x(x - 1)...(x - j + 1)
+        let mut falling: Vec<i128> = vec![1];
+        let mut numerators = vec![0i128; degree + 1];
+        let mut factorial: i128 = 1;
 
-        get_differences_map(starting_vec, &mut differences_map, 0);
+        for (j, &difference) in self.leading_differences.iter().enumerate() {
+            if j > 0 {
+                let mut next = vec![0i128; j + 1];
+                for (i, &coefficient) in falling.iter().enumerate() {
+                    next[i + 1] = next[i + 1].checked_add(coefficient).ok_or(ExtrapolationError::Overflow)?;
+                    next[i] = coefficient.checked_mul(1 - j as i128)
+                        .and_then(|term| next[i].checked_add(term))
+                        .ok_or(ExtrapolationError::Overflow)?;
+                }
+                falling = next;
+            }
+
+            // Bring every term to the common denominator degree! before summing
+            let scale = ((j + 1)..=degree)
+                .try_fold(1i128, |product, factor| product.checked_mul(factor as i128))
+                .ok_or(ExtrapolationError::Overflow)?;
+            for (i, &coefficient) in falling.iter().enumerate() {
+                numerators[i] = coefficient.checked_mul(difference)
+                    .and_then(|term| term.checked_mul(scale))
+                    .and_then(|term| numerators[i].checked_add(term))
+                    .ok_or(ExtrapolationError::Overflow)?;
+            }
+            factorial = factorial.checked_mul(j.max(1) as i128).ok_or(ExtrapolationError::Overflow)?;
+        }
 
-        get_new_value(&differences_map, false)
-    })
-    .sum()
+        Ok(numerators.into_iter()
+            .map(|numerator| Rational::new(numerator, factorial))
+            .collect())
+    }
 }
 
-fn part_2(
-    contents: &String
-) -> i64 {
+fn parse_sequences(contents: &str) -> Vec<Vec<i64>> {
     let reg = Regex::new(r"-?\d+").unwrap();
 
-    contents.lines().map(|line| {
-        let mut differences_map: BTreeMap<u8, Vec<i64>> = BTreeMap::new();
-        
-        let starting_vec = reg.captures_iter(line)
-            .map(|c| c.get(0).unwrap().as_str().parse::<i64>().unwrap())
-            .collect_vec();
+    contents.lines()
+        .map(|line| {
+            reg.captures_iter(line)
+                .map(|c| c.get(0).unwrap().as_str().parse::<i64>().unwrap())
+                .collect_vec()
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct SequenceReport {
+    degree: usize,
+    next_value: i128,
+    previous_value: i128,
+}
 
-        get_differences_map(starting_vec, &mut differences_map, 0);
+fn analyse_sequences(contents: &str) -> Vec<Result<SequenceReport, ExtrapolationError>> {
+    parse_sequences(contents).iter()
+        .map(|values| {
+            let sequence = NewtonSequence::new(values)?;
+            Ok(SequenceReport {
+                degree: sequence.degree(),
+                next_value: sequence.value_at(values.len() as i128)?,
+                previous_value: sequence.value_at(-1)?,
+            })
+        })
+        .collect()
+}
 
-        get_new_value(&differences_map, true)
-    })
-    .sum()
+fn sum_values_at(
+    contents: &str,
+    index: impl Fn(&[i64]) -> i128
+) -> Result<i128, ExtrapolationError> {
+    parse_sequences(contents).iter()
+        .try_fold(0i128, |sum, values| {
+            let value = NewtonSequence::new(values)?.value_at(index(values))?;
+            sum.checked_add(value).ok_or(ExtrapolationError::Overflow)
+        })
+}
+
+fn part_1(
+    contents: &str
+) -> Result<i128, ExtrapolationError> {
+    sum_values_at(contents, |values| values.len() as i128)
+}
+
+fn part_2(
+    contents: &str
+) -> Result<i128, ExtrapolationError> {
+    sum_values_at(contents, |_| -1)
 }
 
 #[cfg(test)]
@@ -91,7 +260,7 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_1(&contents), 114);
+        assert_eq!(part_1(&contents), Ok(114));
     }
     
     #[test]
@@ -100,6 +269,39 @@ mod test {
 
         let contents = fs::read_to_string(file_path).unwrap();
 
-        assert_eq!(part_2(&contents), 2);
+        assert_eq!(part_2(&contents), Ok(2));
+    }
+
+    #[test]
+    fn test_newton_sequence() {
+        let sequence = NewtonSequence::new(&[1, 3, 6, 10, 15, 21]).unwrap();
+
+        assert_eq!(sequence.degree(), 2);
+        assert_eq!(sequence.value_at(6), Ok(28));
+        assert_eq!(sequence.value_at(-1), Ok(0));
+        assert_eq!(sequence.value_at(-5), Ok(6));
+        assert_eq!(sequence.value_at(1_000_000), Ok(500_001_500_001));
+        assert_eq!(
+            sequence.coefficients(),
+            Ok(vec![Rational::new(1, 1), Rational::new(3, 2), Rational::new(1, 2)])
+        );
+
+        let big = NewtonSequence::new(&[0, 1, 8, 27, 64]).unwrap();
+        assert_eq!(big.value_at(i64::MAX as i128), Err(ExtrapolationError::Overflow));
+        assert_eq!(big.value_at(i128::MIN), Err(ExtrapolationError::Overflow));
+    }
+
+    #[test]
+    fn test_analyse_sequences() {
+        let contents = "0 3 6 9 12 15\n1 2 4 8 16\n7\n\n5 5";
+
+        assert_eq!(analyse_sequences(contents), vec![
+            Ok(SequenceReport { degree: 1, next_value: 18, previous_value: -3 }),
+            Err(ExtrapolationError::NotPolynomial),
+            Err(ExtrapolationError::TooFewValues(1)),
+            Err(ExtrapolationError::TooFewValues(0)),
+            Ok(SequenceReport { degree: 0, next_value: 5, previous_value: 5 }),
+        ]);
+        assert_eq!(part_1(contents), Err(ExtrapolationError::NotPolynomial));
     }
 }